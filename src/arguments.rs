@@ -0,0 +1,14 @@
+//! Command line arguments of the game.
+
+use std::path::PathBuf;
+
+/// Getting the path given as `<flag> <path>`, e.g. `--pack ./my_pack`.
+pub fn path_argument(flag: &str) -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
@@ -0,0 +1,85 @@
+//! Checking stage files without launching the game.
+//!
+//! Usage: boxfish-lint [FILE or DIRECTORY]...
+//!
+//! Without arguments, every stage in `assets/stages` is checked.
+//! For a directory with `pack.toml`, the stages listed in it are checked.
+
+use boxfish_overflow::{
+    stage::lint::{Severity, lint_stage},
+    stage_manager::pack::{MANIFEST_NAME, PackStage, StagePack},
+};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const DEFAULT_STAGE_DIRECTORY: &str = "assets/stages";
+
+/// Collecting stage files from a file, a stage pack or a directory.
+fn collect_stages(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if path.join(MANIFEST_NAME).is_file() {
+        let pack = StagePack::load(path).map_err(|e| e.to_string())?;
+        return Ok(pack
+            .stages
+            .into_iter()
+            .filter_map(|stage| match stage {
+                PackStage::File(path) => Some(path),
+                PackStage::Embedded(_) => None,
+            })
+            .collect());
+    }
+    let mut stages = std::fs::read_dir(path)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .collect::<Vec<PathBuf>>();
+    stages.sort();
+    Ok(stages)
+}
+
+fn main() -> ExitCode {
+    let mut targets: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if targets.is_empty() {
+        targets.push(PathBuf::from(DEFAULT_STAGE_DIRECTORY));
+    }
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for target in targets {
+        let stages = match collect_stages(&target) {
+            Ok(stages) => stages,
+            Err(e) => {
+                eprintln!("{}: error: {}", target.display(), e);
+                errors += 1;
+                continue;
+            }
+        };
+        for stage in stages {
+            let source = match std::fs::read_to_string(&stage) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("{}: error: {}", stage.display(), e);
+                    errors += 1;
+                    continue;
+                }
+            };
+            for diagnostic in lint_stage(&source) {
+                match diagnostic.severity {
+                    Severity::Warning => warnings += 1,
+                    Severity::Error => errors += 1,
+                }
+                println!("{}:{}", stage.display(), diagnostic);
+            }
+        }
+    }
+    println!("{} error(s), {} warning(s)", errors, warnings);
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -16,6 +16,7 @@ impl Plugin for PlayerPlugin {
             .init_resource::<BooleanImage>()
             .init_resource::<PlayerImage>()
             .init_resource::<ResultManager>()
+            .init_resource::<CurrentPuzzle>()
             .add_plugins(MovementPlugin)
             .add_systems(
                 Startup,
@@ -27,8 +28,10 @@ impl Plugin for PlayerPlugin {
                 (
                     visual::face_manager,
                     register::hightlight_incorresponded_gate,
-                    register::process_gate_effect,
                     register::bit_visualise,
+                    sync_with_puzzle.run_if(resource_changed::<CurrentPuzzle>),
+                    construction::resize_body.run_if(resource_changed::<CurrentPuzzle>),
+                    visual::draw_resting_boxfish.run_if(resource_changed::<CurrentPuzzle>),
                     reset_result.run_if(on_event::<NewGame>),
                 ),
             );
@@ -43,7 +46,6 @@ pub struct Body;
 #[derive(Component, Clone)]
 pub struct BoxfishRegister {
     boolean: bool,
-    history: Vec<bool>,
 }
 
 #[derive(Component)]
@@ -57,17 +59,63 @@ pub struct Tail;
 #[derive(Component)]
 pub struct Head {
     is_expanding: bool,
-    history: Vec<IVec2>,
 }
 
 #[derive(Component)]
 pub struct Player;
 
+#[derive(Resource, Default, Deref, DerefMut)]
+/// The puzzle of the stage currently played.
+///
+/// Components of the boxfish are just copies of this.
+pub struct CurrentPuzzle(pub PuzzleState);
+
 #[derive(Resource, Default)]
 pub struct ResultManager {
     pub steps: u32,
+    /// Pearls picked up in cleared stages.
+    pub pearls: u32,
 }
 
 pub fn reset_result(mut r_manager: ResMut<ResultManager>) {
     r_manager.steps = 0;
+    r_manager.pearls = 0;
+}
+
+/// Getting how a boxfish is turned and whether it's mirrored, since the sprite faces right.
+/// Facing left mirrors it, as turning it around would draw it upside down.
+pub fn facing_to_pose(facing: IVec2) -> (Quat, bool) {
+    if facing.y == 0 {
+        return (Quat::IDENTITY, facing.x < 0);
+    }
+    (
+        Quat::from_rotation_z(Vec2::X.angle_to(facing.as_vec2())),
+        false,
+    )
+}
+
+/// Copying the state of [CurrentPuzzle] into the boxfish's components.
+pub fn sync_with_puzzle(
+    puzzle: Res<CurrentPuzzle>,
+    mut head_query: Query<(&mut TileCoords, &mut Head, &mut Transform)>,
+    mut bit_query: Query<(&BitIter, &mut BoxfishRegister, &mut Transform), Without<Head>>,
+) {
+    // The body follows the head's rotation as its children.
+    // Mirroring the head's scale rather than its sprite keeps the body trailing behind it.
+    let (rotation, mirrored) = facing_to_pose(puzzle.facing());
+    let scale_x = if mirrored { -1. } else { 1. };
+    for (mut tile_coords, mut head, mut transform) in &mut head_query {
+        tile_coords.tile_pos = puzzle.head();
+        head.is_expanding = puzzle.is_expanding();
+        transform.rotation = rotation;
+        transform.scale.x = scale_x;
+    }
+    for (bit_iter, mut register, mut transform) in &mut bit_query {
+        if let Some(bit) = puzzle.bits().get(bit_iter.pos) {
+            register.boolean = *bit;
+        }
+        // Keeping numbers upright and unmirrored
+        transform.rotation = rotation.inverse();
+        transform.scale.x = scale_x;
+    }
 }
@@ -1,4 +1,4 @@
-use crate::boxfish::{BooleanImage, visual::PlayerImage};
+use crate::boxfish::{BooleanImage, movement::expansion::Expanding, visual::PlayerImage};
 use crate::prelude::*;
 use bevy::prelude::*;
 
@@ -11,7 +11,6 @@ pub fn spawn_boxfishs_head(mut commands: Commands, player_image: Res<PlayerImage
         Transform::from_xyz(0., 0., PLAYER_LAYER),
         Head {
             is_expanding: false,
-            history: Vec::new(),
         },
         Player,
         TileCoords {
@@ -30,6 +29,7 @@ pub fn update_player_to_just_loaded_stage(
         &mut Head,
     )>,
     mut construct_aquarium: EventReader<ConstructAquarium>,
+    mut puzzle: ResMut<CurrentPuzzle>,
     mut commands: Commands,
     player_image: Res<PlayerImage>,
     boolean_image: Res<BooleanImage>,
@@ -39,6 +39,8 @@ pub fn update_player_to_just_loaded_stage(
         Some(aq) => aq,
         None => return,
     };
+    // Rebuilding the puzzle
+    **puzzle = PuzzleState::from(aquarium);
     // Getting the head of the boxfish
     let mut head = match head_query.single_mut() {
         Ok(h) => h,
@@ -59,43 +61,86 @@ pub fn update_player_to_just_loaded_stage(
     }
     // Generating new bits and a tail
     commands.entity(head.0).with_children(|parent| {
-        let body_length = aquarium.player_defaultbits.len();
-        let bit_transform = Transform::from_xyz(0., 0., PLAYER_LAYER);
-        let tail_transform = Transform::from_xyz(-(TILE_SIZE as f32), 0., PLAYER_LAYER);
+        spawn_body(
+            parent,
+            &aquarium.player_defaultbits,
+            false,
+            &player_image,
+            &boolean_image,
+        );
+    });
+}
 
-        for (iter, bit) in aquarium.player_defaultbits.iter().enumerate() {
-            parent
-                .spawn((
-                    player_image.index_to_sprite(1, 0),
-                    bit_transform.clone(),
-                    Body,
-                    BitIter { pos: iter },
-                    Player,
-                ))
-                .with_child((
-                    boolean_image.y_to_sprite(0),
-                    bit_transform.clone(),
-                    BoxfishRegister {
-                        boolean: *bit,
-                        history: Vec::new(),
-                    },
-                    BitIter { pos: iter },
-                    Player,
-                ));
-        }
-        // Spawn a tail
-        parent.spawn((
-            player_image.index_to_sprite(0, 0),
-            tail_transform,
+/// Respawning bits and a tail when a gate changed the length of the register.
+pub fn resize_body(
+    puzzle: Res<CurrentPuzzle>,
+    head_query: Query<(Entity, &Children), With<Head>>,
+    tail_query: Query<&BitIter, With<Tail>>,
+    mut commands: Commands,
+    player_image: Res<PlayerImage>,
+    boolean_image: Res<BooleanImage>,
+) {
+    let (Ok(tail), Ok((head, children))) = (tail_query.single(), head_query.single()) else {
+        return;
+    };
+    if tail.pos == puzzle.bits().len() {
+        return;
+    }
+    for child in children {
+        commands.entity(*child).despawn();
+    }
+    commands.entity(head).with_children(|parent| {
+        spawn_body(
+            parent,
+            puzzle.bits(),
+            puzzle.is_expanding(),
+            &player_image,
+            &boolean_image,
+        );
+    });
+}
+
+/// Spawning bits and a tail as children of the head.
+fn spawn_body(
+    parent: &mut ChildSpawnerCommands,
+    bits: &[bool],
+    is_expanding: bool,
+    player_image: &PlayerImage,
+    boolean_image: &BooleanImage,
+) {
+    let body_length = bits.len();
+    let bit_transform = Transform::from_xyz(0., 0., PLAYER_LAYER);
+    let tail_transform = Transform::from_xyz(-(TILE_SIZE as f32), 0., PLAYER_LAYER);
+
+    for (iter, bit) in bits.iter().enumerate() {
+        let mut body = parent.spawn((
+            player_image.index_to_sprite(1, 0),
+            bit_transform.clone(),
             Body,
-            BitIter { pos: body_length },
-            Tail,
+            BitIter { pos: iter },
             Player,
         ));
-    });
-
-    // Clear movement history
-    for mut head in head_query {
-        head.4.history = Vec::new();
+        body.with_child((
+            boolean_image.y_to_sprite(0),
+            bit_transform.clone(),
+            BoxfishRegister { boolean: *bit },
+            BitIter { pos: iter },
+            Player,
+        ));
+        if is_expanding {
+            body.insert(Expanding::default());
+        }
+    }
+    // Spawn a tail
+    let mut tail = parent.spawn((
+        player_image.index_to_sprite(0, 0),
+        tail_transform,
+        Body,
+        BitIter { pos: body_length },
+        Tail,
+        Player,
+    ));
+    if is_expanding {
+        tail.insert(Expanding::default());
     }
 }
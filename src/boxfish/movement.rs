@@ -1,14 +1,16 @@
 pub mod collision;
 pub mod expansion;
 pub mod input;
+pub mod warp;
 
 use crate::{
     boxfish::{
-        BoxfishRegister, PLAYER_LAYER, ResultManager,
-        movement::{collision::CollisionSoundEffect, input::player_input},
+        PLAYER_LAYER, ResultManager,
+        movement::{collision::CollisionSoundEffect, expansion::Expanding, input::PlayerControls},
+        register::GateCollidedAt,
     },
     prelude::*,
-    stage_manager::StageInfo,
+    replay::{Operated, Operation},
 };
 use bevy::prelude::*;
 pub use collision::PlayerCollidedAnimation;
@@ -18,8 +20,16 @@ pub struct MovementPlugin;
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CollisionSoundEffect>()
+            .init_resource::<warp::WarpSoundEffect>()
             .add_event::<OnMoved>()
-            .add_systems(Startup, collision::init_collision_sound_effect)
+            .add_event::<warp::Warped>()
+            .add_systems(
+                Startup,
+                (
+                    collision::init_collision_sound_effect,
+                    warp::init_warp_sound_effect,
+                ),
+            )
             .add_systems(
                 Update,
                 (
@@ -33,13 +43,24 @@ impl Plugin for MovementPlugin {
                 (
                     get_player_input,
                     move_to_ideal_position,
-                    regist_movement_history,
                     step_counter,
                     collision::goal_detection_system,
+                    collision::highlight_mismatched_goals.run_if(resource_changed::<CurrentPuzzle>),
                     expansion::get_expand_input,
-                    undo,
+                    switch_boxfish,
+                    (warp::detect_warp, warp::on_warped)
+                        .chain()
+                        .after(get_player_input),
                 )
                     .run_if(in_state(MacroStates::GamePlay)),
+            )
+            .add_systems(
+                Update,
+                (
+                    undo_and_redo,
+                    watch_remaining_steps.run_if(resource_changed::<CurrentPuzzle>),
+                )
+                    .run_if(in_state(MacroStates::GamePlay).or(in_state(MacroStates::OutOfMoves))),
             );
     }
 }
@@ -49,20 +70,14 @@ impl Plugin for MovementPlugin {
 ///
 /// This is not emitted if the move was blocked (e.g., by a wall).
 pub struct OnMoved {
+    /// Where the head was before moving.
+    pub from: IVec2,
     pub travel: Travel,
 }
 
 /// How many seconds will the boxfish take to move a tile.
 const SECONDS_PER_TILE: f32 = 0.2;
 
-fn get_body_length(body_query: &Query<&BitIter, With<Body>>, head: &Head) -> usize {
-    body_query
-        .iter()
-        .map(|bit_iter| 1 + if head.is_expanding { bit_iter.pos } else { 1 })
-        .max()
-        .unwrap_or(1)
-}
-
 /// Move the boxfish by the player's operation.
 pub fn get_player_input(
     mut commands: Commands,
@@ -70,14 +85,13 @@ pub fn get_player_input(
         (&mut Transform, &mut TileCoords, Entity, &Head),
         Without<PlayerCollidedAnimation>,
     >,
-    body_query: Query<&BitIter, With<Body>>,
-    stage_info: Res<StageInfo>,
+    mut puzzle: ResMut<CurrentPuzzle>,
     mut on_moved: EventWriter<OnMoved>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    gamepad_input: Query<&Gamepad>,
+    mut gate_collided_at_writer: EventWriter<GateCollidedAt>,
+    mut operated: EventWriter<Operated>,
+    mut controls: PlayerControls,
 ) {
-    if let Ok((mut transform, mut tile, entity, head)) = player_query.single_mut() {
-        let body_length = get_body_length(&body_query, &head);
+    if let Ok((mut transform, mut tile, entity, _)) = player_query.single_mut() {
         let target_pos = TileCoords::ivec2_to_vec2(tile.tile_pos);
         let current_pos = transform.translation.xy();
         let difference = target_pos - current_pos;
@@ -91,35 +105,37 @@ pub fn get_player_input(
         transform.translation.x = target_pos.x;
         transform.translation.y = target_pos.y;
 
-        let direction = player_input(&keyboard_input, &gamepad_input);
+        let direction = controls.travel();
 
         if direction.amount == 0 {
             return;
         }
 
-        let collision = if !head.is_expanding {
-            // If the boxfish wasn't expanding,
-            // take collisions and semicollisions as colliding targets.
-            stage_info.collisions.clone() + stage_info.semicollisions.clone()
-        } else {
-            // Take only collisions as colliding target
-            // on the boxfish is expanding.
-            stage_info.collisions.clone()
-        };
-        // If any part of the boxfish, was_collided will be true
-        let was_collided = (0..(body_length + 1)).any(|iter| {
-            collision.do_collide(&(tile.tile_pos - IVec2::new(iter as i32, 0)), &direction)
-        });
-        if !was_collided {
-            // Move when the boxfish didn't collided anywhere
-            tile.tile_pos += direction.into_ivec2();
-            on_moved.write(OnMoved { travel: direction });
-        } else {
-            // Play animation on the boxfish collided
-            commands.entity(entity).insert(PlayerCollidedAnimation {
-                progress: 0.,
-                travel: direction,
-            });
+        let from = puzzle.head();
+        match puzzle.play(Action::Move(direction.clone())) {
+            Outcome::Moved | Outcome::Goal => {
+                tile.tile_pos = puzzle.head();
+                if let Some(operation) = Operation::from_travel(&direction) {
+                    operated.write(Operated(operation));
+                }
+                on_moved.write(OnMoved {
+                    from,
+                    travel: direction,
+                });
+            }
+            outcome => {
+                // Highlight gates which didn't correspond to the register
+                if let Outcome::BouncedByEqualGate { gates } = outcome {
+                    for collided_at in gates {
+                        gate_collided_at_writer.write(GateCollidedAt { collided_at });
+                    }
+                }
+                // Play animation on the boxfish collided
+                commands.entity(entity).insert(PlayerCollidedAnimation {
+                    progress: 0.,
+                    travel: direction,
+                });
+            }
         }
     }
 }
@@ -156,42 +172,77 @@ pub fn move_to_ideal_position(
     }
 }
 
-/// Regist the last coords before moving to the history.
-pub fn regist_movement_history(
-    mut query: Query<(&mut Head, &TileCoords)>,
-    mut travel: EventReader<OnMoved>,
+/// Undo the last operation when Ctrl+Z pressed, and redo the last undone
+/// operation when Ctrl+Y or Ctrl+Shift+Z pressed.
+pub fn undo_and_redo(
+    mut commands: Commands,
+    head_query: Query<(&mut TileCoords, &mut Transform), With<Head>>,
+    body_query: Query<Entity, With<Body>>,
+    mut puzzle: ResMut<CurrentPuzzle>,
+    mut operated: EventWriter<Operated>,
+    mut controls: PlayerControls,
 ) {
-    for read in travel.read() {
-        if let Ok((mut head, coords)) = query.single_mut() {
-            head.history
-                .push(coords.tile_pos - read.travel.into_ivec2());
-        }
+    let operation = if controls.undo() {
+        puzzle.undo().then_some(Operation::Undo)
+    } else if controls.redo() {
+        puzzle.redo().then_some(Operation::Redo)
+    } else {
+        None
+    };
+    let Some(operation) = operation else {
+        return;
+    };
+    operated.write(Operated(operation));
+    snap_to_puzzle(&mut commands, head_query, body_query, &puzzle);
+}
+
+/// Controlling the next boxfish when the switch button pressed.
+pub fn switch_boxfish(
+    mut commands: Commands,
+    head_query: Query<(&mut TileCoords, &mut Transform), With<Head>>,
+    body_query: Query<Entity, With<Body>>,
+    mut puzzle: ResMut<CurrentPuzzle>,
+    mut operated: EventWriter<Operated>,
+    mut controls: PlayerControls,
+) {
+    if !controls.switch_just_pressed() || puzzle.play(Action::Switch) == Outcome::Blocked {
+        return;
     }
+    operated.write(Operated(Operation::Switch));
+    snap_to_puzzle(&mut commands, head_query, body_query, &puzzle);
 }
 
-/// Undo the last operation when Ctrl+Z pressed, by reproducting the last status.
-pub fn undo(
-    head_query: Query<(&mut TileCoords, &mut Transform, &mut Head)>,
-    bit_query: Query<&mut BoxfishRegister>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    gamepad: Query<&Gamepad>,
+/// Stopping the gameplay when the boxfish took every step the stage allows,
+/// and going back to it when steps are given back.
+pub fn watch_remaining_steps(
+    puzzle: Res<CurrentPuzzle>,
+    state: Res<State<MacroStates>>,
+    mut next_state: ResMut<NextState<MacroStates>>,
 ) {
-    let do_undo = match gamepad.single() {
-        Ok(gamepad) => gamepad.just_pressed(GamepadButton::South),
-        Err(_) => false,
-    } | keyboard_input.pressed(KeyCode::ControlLeft)
-        && keyboard_input.just_pressed(KeyCode::KeyZ);
-    if do_undo {
-        for (mut t_coords, mut transform, mut head) in head_query {
-            if let Some(last) = head.history.pop() {
-                t_coords.tile_pos = last;
-                transform.translation = TileCoords::ivec2_to_vec2(last).extend(PLAYER_LAYER);
-            }
-        }
-        for mut register in bit_query {
-            if let Some(last) = register.history.pop() {
-                register.boolean = last;
-            }
+    match (state.get(), puzzle.out_of_steps()) {
+        (MacroStates::GamePlay, true) => next_state.set(MacroStates::OutOfMoves),
+        (MacroStates::OutOfMoves, false) => next_state.set(MacroStates::GamePlay),
+        _ => (),
+    }
+}
+
+/// Putting the boxfish where the puzzle is at once, without any animation.
+pub fn snap_to_puzzle(
+    commands: &mut Commands,
+    head_query: Query<(&mut TileCoords, &mut Transform), With<Head>>,
+    body_query: Query<Entity, With<Body>>,
+    puzzle: &PuzzleState,
+) {
+    for (mut t_coords, mut transform) in head_query {
+        t_coords.tile_pos = puzzle.head();
+        transform.translation = TileCoords::ivec2_to_vec2(puzzle.head()).extend(PLAYER_LAYER);
+    }
+    // Expansion may be restored too
+    for entity in body_query {
+        if puzzle.is_expanding() {
+            commands.entity(entity).insert(Expanding::default());
+        } else {
+            commands.entity(entity).remove::<Expanding>();
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::boxfish::{ResultManager, register::GateCollidedAt};
 use crate::prelude::*;
 use crate::stage_manager::NextStage;
 use bevy::{audio::Volume, prelude::*};
@@ -38,6 +39,10 @@ impl From<Vec<IVec2>> for Collision {
 }
 
 impl Collision {
+    /// 座標が衝突対象に含まれるかを取得する
+    pub fn contains(&self, pos: &IVec2) -> bool {
+        self.collision.contains(pos)
+    }
     /// 複数の対象に対して衝突判定を行う
     pub fn do_collide(&self, original: &IVec2, travel: &Travel) -> bool {
         self.collision
@@ -141,31 +146,38 @@ pub fn collided_animation(
     }
 }
 
+/// Completing the stage when the boxfish reached a goal which the register fits.
 pub fn goal_detection_system(
     mut commands: Commands,
-    head_query: Query<(&Head, &TileCoords)>,
-    bits: Query<&BitIter>,
+    puzzle: Res<CurrentPuzzle>,
     goals: Query<(&Goal, &TileCoords, Entity), Without<StageCompleted>>,
     mut next_stage: EventWriter<NextStage>,
+    mut r_manager: ResMut<ResultManager>,
 ) {
-    let (head, tile_coords) = if let Ok((head, tile_coords)) = head_query.single() {
-        (head, tile_coords)
-    } else {
+    if !puzzle.reached_goal() {
         return;
-    };
-    let bit_iters: Vec<usize> = if head.is_expanding {
-        bits.iter().map(|bit| bit.pos).collect()
-    } else {
-        (0..2).collect()
-    };
-    let player_coods = bit_iters
-        .iter()
-        .map(|i| tile_coords.tile_pos - IVec2::new(*i as i32, 0))
-        .collect::<Vec<IVec2>>();
+    }
+    let player_coods = puzzle.occupied_cells();
+    let mut cleared = false;
     for (_, pos, entity) in goals {
-        if player_coods.contains(&pos.tile_pos) {
+        if player_coods.contains(&pos.tile_pos) && puzzle.fits_goal(pos.tile_pos) {
             commands.entity(entity).insert(StageCompleted);
-            next_stage.write(NextStage);
+            cleared = true;
         }
     }
+    // Reaching several goals at once clears the stage only once
+    if cleared {
+        next_stage.write(NextStage);
+        r_manager.pearls += puzzle.collected_pearls();
+    }
+}
+
+/// Highlighting goals which the boxfish reached with a wrong register.
+pub fn highlight_mismatched_goals(
+    puzzle: Res<CurrentPuzzle>,
+    mut gate_collided_at: EventWriter<GateCollidedAt>,
+) {
+    for collided_at in puzzle.mismatched_goals() {
+        gate_collided_at.write(GateCollidedAt { collided_at });
+    }
 }
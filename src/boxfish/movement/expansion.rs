@@ -1,9 +1,13 @@
-use crate::{boxfish::movement::PlayerCollidedAnimation, prelude::*, stage_manager::StageInfo};
+use crate::{
+    boxfish::movement::{PlayerCollidedAnimation, input::PlayerControls},
+    prelude::*,
+    replay::{Operated, Operation},
+};
 use bevy::prelude::*;
 
 /// Bodyにつけられるコンポーネント
 /// ついてると膨らみ中
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct Expanding {
     // BitIter
     collided_at: Option<usize>,
@@ -12,57 +16,54 @@ pub struct Expanding {
 /// 伸び縮みのキー入力を受け取る
 pub fn get_expand_input(
     mut commands: Commands,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut head_query: Query<(&mut Head, &TileCoords)>,
-    stage_info: Res<StageInfo>,
-    body_query: Query<(&Body, &BitIter, Option<&Tail>, Entity)>,
-    gamepad_query: Query<&Gamepad>,
+    mut controls: PlayerControls,
+    mut head_query: Query<&mut Head>,
+    mut puzzle: ResMut<CurrentPuzzle>,
+    body_query: Query<Entity, With<Body>>,
+    mut operated: EventWriter<Operated>,
 ) {
-    const EXPAND_KEY: KeyCode = KeyCode::Enter;
     // 膨らむボタンが押されたときのフラグ
-    let just_pressed = match gamepad_query.single() {
-        Ok(gamepad) => gamepad.just_pressed(GamepadButton::East),
-        Err(_) => false,
-    } | keyboard_input.just_pressed(EXPAND_KEY);
+    let just_pressed = controls.expand_just_pressed();
     // 膨らむボタンが離されたときのフラグ
-    let just_released = match gamepad_query.single() {
-        Ok(gamepad) => gamepad.just_released(GamepadButton::East),
-        Err(_) => false,
-    } | keyboard_input.just_released(EXPAND_KEY);
+    let just_released = controls.expand_just_released();
 
     if just_pressed {
-        for (_, tile_coords) in &head_query {
-            // 尻尾含めたBodyの最大のBitIter、すなわち体の長さを取得
-            let body_len = body_query.iter().map(|b| b.1.pos).max().unwrap_or(0);
-            // 衝突位置を取得
-            let collided_at = stage_info
-                .collisions
-                .collide_at(
-                    &tile_coords.tile_pos,
-                    &Travel {
-                        direction: Direction::X,
-                        amount: -((body_len as i32) + 1),
-                    },
-                )
-                .map(|at| (tile_coords.tile_pos - at).x as usize);
-            // BodyにExpandingコンポーネントを追加
-            // キーボードでの処理
-            for (_, _, _, entity) in body_query {
-                commands.entity(entity).insert(Expanding { collided_at });
+        operated.write(Operated(Operation::Expand));
+        // 衝突位置を取得
+        let collided_at = match puzzle.expansion_reach() {
+            // 壁にぶつからないなら実際に膨らむ
+            // 敵の魚に触れたら、そこで壁のようにぶつかる
+            None => match puzzle.play(Action::Expand) {
+                Outcome::Caught { enemies } => enemies
+                    .iter()
+                    .map(|at| (puzzle.head() - *at).abs().element_sum() as usize)
+                    .min(),
+                _ => None,
+            },
+            reach => reach,
+        };
+        // ぶつかるなら見た目だけ膨らみ、on_expandingで元に戻る
+        if collided_at.is_some() {
+            for mut head in &mut head_query {
+                head.is_expanding = true;
             }
         }
-        // 頭のフラグを更新
-        for (mut head, _) in &mut head_query {
-            head.is_expanding = true;
+        // BodyにExpandingコンポーネントを追加
+        for entity in body_query {
+            commands.entity(entity).insert(Expanding { collided_at });
         }
     }
     // Shiftが離されたらExpandingコンポーネントを削除
     if just_released {
-        for (_, _, _, entity) in body_query {
+        operated.write(Operated(Operation::Shrink));
+        for entity in body_query {
             commands.entity(entity).remove::<Expanding>();
         }
+        if puzzle.is_expanding() {
+            puzzle.play(Action::Shrink);
+        }
         // 頭のフラグを更新
-        for (mut head, _) in &mut head_query {
+        for mut head in &mut head_query {
             head.is_expanding = false;
         }
     }
@@ -77,6 +78,7 @@ pub fn on_expanding(
     query: Query<(&BitIter, &Expanding, &mut Transform, Option<&Tail>), With<Body>>,
     exp_query: Query<Entity, With<Expanding>>,
     mut head_query: Query<(&mut Head, Entity)>,
+    puzzle: Res<CurrentPuzzle>,
 ) {
     let max_iter = query.iter().map(|q| q.0.pos).max().unwrap_or(0);
     for (bit_iter, expanding, mut transform, tail) in query {
@@ -103,10 +105,7 @@ pub fn on_expanding(
                     commands
                         .entity(head_entity)
                         .insert(PlayerCollidedAnimation {
-                            travel: Travel {
-                                direction: Direction::X,
-                                amount: 1,
-                            },
+                            travel: Travel::from_ivec2(puzzle.facing()),
                             progress: 0.,
                         });
                     head.is_expanding = false;
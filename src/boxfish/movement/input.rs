@@ -1,6 +1,7 @@
-use bevy::prelude::*;
+use crate::replay::{Operation, ReplayPlayback};
+use bevy::{ecs::system::SystemParam, prelude::*};
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Travel {
     pub direction: Direction,
     pub amount: i32,
@@ -35,13 +36,27 @@ impl std::fmt::Display for Travel {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Direction {
     X,
     Y,
 }
 
 impl Travel {
+    /// Getting a travel along an axis, the vector should have only x or y.
+    pub fn from_ivec2(vec: IVec2) -> Self {
+        if vec.x != 0 {
+            Travel {
+                direction: Direction::X,
+                amount: vec.x,
+            }
+        } else {
+            Travel {
+                direction: Direction::Y,
+                amount: vec.y,
+            }
+        }
+    }
     pub fn into_ivec2(&self) -> IVec2 {
         match self.direction {
             Direction::X => IVec2::new(self.amount, 0),
@@ -115,3 +130,85 @@ pub fn player_input(
         }
     }
 }
+
+/// The key to expand the boxfish while holding.
+const EXPAND_KEY: KeyCode = KeyCode::Enter;
+
+#[derive(SystemParam)]
+/// Operations of the player from the keyboard or a gamepad,
+/// or from a replay while it's played back.
+pub struct PlayerControls<'w, 's> {
+    keyboard_input: Res<'w, ButtonInput<KeyCode>>,
+    gamepad: Query<'w, 's, &'static Gamepad>,
+    playback: ResMut<'w, ReplayPlayback>,
+}
+
+impl PlayerControls<'_, '_> {
+    /// Where the player is going to move, its amount is 0 when not moving.
+    pub fn travel(&mut self) -> Travel {
+        if self.playback.is_playing() {
+            return self.playback.take_travel().unwrap_or(Travel {
+                direction: Direction::X,
+                amount: 0,
+            });
+        }
+        player_input(&self.keyboard_input, &self.gamepad)
+    }
+    /// Was the expand button just pressed? (Enter or East button)
+    pub fn expand_just_pressed(&mut self) -> bool {
+        if self.playback.is_playing() {
+            return self.playback.take(Operation::Expand);
+        }
+        self.gamepad_just(GamepadButton::East, true) | self.keyboard_input.just_pressed(EXPAND_KEY)
+    }
+    /// Was the expand button just released?
+    pub fn expand_just_released(&mut self) -> bool {
+        if self.playback.is_playing() {
+            return self.playback.take(Operation::Shrink);
+        }
+        self.gamepad_just(GamepadButton::East, false)
+            | self.keyboard_input.just_released(EXPAND_KEY)
+    }
+    /// Was switching to another boxfish required? (Space or left trigger)
+    pub fn switch_just_pressed(&mut self) -> bool {
+        if self.playback.is_playing() {
+            return self.playback.take(Operation::Switch);
+        }
+        self.gamepad_just(GamepadButton::LeftTrigger, true)
+            || self.keyboard_input.just_pressed(KeyCode::Space)
+    }
+    /// Was undo required? (Ctrl+Z or South button)
+    pub fn undo(&mut self) -> bool {
+        if self.playback.is_playing() {
+            return self.playback.take(Operation::Undo);
+        }
+        self.gamepad_just(GamepadButton::South, true)
+            || (self.ctrl() && !self.shift() && self.keyboard_input.just_pressed(KeyCode::KeyZ))
+    }
+    /// Was redo required? (Ctrl+Y, Ctrl+Shift+Z or right trigger)
+    pub fn redo(&mut self) -> bool {
+        if self.playback.is_playing() {
+            return self.playback.take(Operation::Redo);
+        }
+        self.gamepad_just(GamepadButton::RightTrigger, true)
+            || (self.ctrl()
+                && (self.keyboard_input.just_pressed(KeyCode::KeyY)
+                    || (self.shift() && self.keyboard_input.just_pressed(KeyCode::KeyZ))))
+    }
+
+    fn gamepad_just(&self, button: GamepadButton, pressed: bool) -> bool {
+        match self.gamepad.single() {
+            Ok(gamepad) if pressed => gamepad.just_pressed(button),
+            Ok(gamepad) => gamepad.just_released(button),
+            Err(_) => false,
+        }
+    }
+    fn ctrl(&self) -> bool {
+        self.keyboard_input
+            .any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    }
+    fn shift(&self) -> bool {
+        self.keyboard_input
+            .any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    }
+}
@@ -0,0 +1,69 @@
+use crate::prelude::*;
+use bevy::{
+    audio::{PlaybackMode, Volume},
+    prelude::*,
+};
+
+#[derive(Event)]
+/// Emitted when the boxfish was carried by a warp tile.
+pub struct Warped {
+    /// The warp tile the head moved onto.
+    pub from: IVec2,
+    /// The partner the head was carried to.
+    pub to: IVec2,
+}
+
+#[derive(Resource, Default)]
+/// The sound of warping, which reuses the sound of expanding.
+/// It's played faster, so it sounds higher than expanding.
+pub struct WarpSoundEffect {
+    warped: Handle<AudioSource>,
+}
+
+/// How many times as fast as expanding the sound of warping is played.
+const WARP_SOUND_SPEED: f32 = 1.6;
+
+pub fn init_warp_sound_effect(
+    mut sound_effect: ResMut<WarpSoundEffect>,
+    asset_server: Res<AssetServer>,
+) {
+    sound_effect.warped = asset_server.load("embedded://sound_effects/extend.wav");
+}
+
+/// Emitting [Warped] when a warp tile carried the head in the move.
+pub fn detect_warp(
+    puzzle: Res<CurrentPuzzle>,
+    mut on_moved: EventReader<OnMoved>,
+    mut warped: EventWriter<Warped>,
+) {
+    for _ in on_moved.read() {
+        if let Some(from) = puzzle.warped_from()
+            && let Some(to) = puzzle.layout().warp_partner(from)
+        {
+            warped.write(Warped { from, to });
+        }
+    }
+}
+
+/// Putting the boxfish on the partner at once, instead of swimming there.
+pub fn on_warped(
+    mut commands: Commands,
+    mut warped: EventReader<Warped>,
+    mut head_query: Query<&mut Transform, With<Head>>,
+    sound_effect: Res<WarpSoundEffect>,
+) {
+    for Warped { to, .. } in warped.read() {
+        for mut transform in &mut head_query {
+            transform.translation = TileCoords::ivec2_to_vec2(*to).extend(PLAYER_LAYER);
+        }
+        commands.spawn((
+            AudioPlayer::new(sound_effect.warped.clone()),
+            PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: Volume::Linear(0.3),
+                speed: WARP_SOUND_SPEED,
+                ..default()
+            },
+        ));
+    }
+}
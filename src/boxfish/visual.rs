@@ -1,4 +1,4 @@
-use crate::boxfish::PlayerCollidedAnimation;
+use crate::boxfish::{PlayerCollidedAnimation, facing_to_pose};
 use crate::prelude::*;
 use bevy::prelude::*;
 
@@ -73,6 +73,63 @@ pub fn assets_setup(
     ));
 }
 
+#[derive(Component)]
+/// A part of a boxfish which isn't controlled now.
+pub struct RestingBoxfish;
+
+/// How bright a boxfish which isn't controlled is.
+const RESTING_BRIGHTNESS: f32 = 0.6;
+
+/// Drawing boxfish which aren't controlled now again, as they're just shown.
+pub fn draw_resting_boxfish(
+    mut commands: Commands,
+    puzzle: Res<CurrentPuzzle>,
+    old_parts: Query<Entity, With<RestingBoxfish>>,
+    player_image: Res<PlayerImage>,
+    boolean_image: Res<BooleanImage>,
+) {
+    for entity in old_parts {
+        commands.entity(entity).despawn();
+    }
+    let tint = Color::srgb(RESTING_BRIGHTNESS, RESTING_BRIGHTNESS, RESTING_BRIGHTNESS);
+    for boxfish in puzzle.others() {
+        let cells = boxfish.body_cells();
+        let (rotation, mirrored) = facing_to_pose(boxfish.facing());
+        let part = |mut sprite: Sprite, cell: IVec2| {
+            sprite.color = tint;
+            sprite.flip_x = mirrored;
+            let translation = TileCoords::ivec2_to_vec2(cell).extend(PLAYER_LAYER);
+            (
+                sprite,
+                Transform::from_translation(translation).with_rotation(rotation),
+                RestingBoxfish,
+            )
+        };
+        let face_kind = if boxfish.is_expanding() { 1 } else { 0 };
+        commands.spawn(part(player_image.index_to_sprite(2, face_kind), cells[0]));
+        // Bits share a cell while packed
+        for (i, bit) in boxfish.bits().iter().enumerate() {
+            let cell = cells[(i + 1).min(cells.len() - 2)];
+            let atlas = if *bit {
+                boolean_image.one()
+            } else {
+                boolean_image.zero()
+            };
+            commands
+                .spawn(part(player_image.index_to_sprite(1, 0), cell))
+                .with_child((
+                    Sprite::from_atlas_image(boolean_image.image.clone(), atlas),
+                    // Keeping numbers upright
+                    Transform::from_xyz(0., 0., PLAYER_LAYER).with_rotation(rotation.inverse()),
+                ));
+        }
+        commands.spawn(part(
+            player_image.index_to_sprite(0, 0),
+            cells[cells.len() - 1],
+        ));
+    }
+}
+
 pub fn face_manager(
     mut query: Query<(&mut Sprite, Option<&PlayerCollidedAnimation>, &Head)>,
     player_image: Res<PlayerImage>,
@@ -0,0 +1,13 @@
+pub mod arguments;
+pub mod boxfish;
+pub mod camera;
+pub mod music;
+pub mod prelude;
+pub mod progress;
+pub mod puzzle;
+pub mod replay;
+pub mod stage;
+pub mod stage_manager;
+pub mod styling;
+pub mod ui;
+pub mod user_data;
@@ -3,21 +3,11 @@
     windows_subsystem = "windows"
 )]
 
-mod boxfish;
-mod camera;
-mod music;
-pub mod prelude;
-mod stage;
-mod stage_manager;
-mod styling;
-mod ui;
-
 use bevy::{image::ImageSamplerDescriptor, prelude::*};
 use bevy_embedded_assets::EmbeddedAssetPlugin;
-use boxfish::PlayerPlugin;
-
-use crate::{
-    camera::CameraPlugin, music::MusicPlugin, prelude::*, stage::AquariumPlugin,
+use boxfish_overflow::{
+    boxfish::PlayerPlugin, camera::CameraPlugin, music::MusicPlugin, prelude::*,
+    progress::ProgressPlugin, replay::ReplayPlugin, stage::AquariumPlugin,
     stage_manager::StageManagerPlugin, styling::StylingPlugin, ui::UIPlugin,
 };
 
@@ -35,5 +25,7 @@ fn main() {
         .add_plugins(UIPlugin)
         .add_plugins(AquariumPlugin)
         .add_plugins(MusicPlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(ProgressPlugin)
         .run();
 }
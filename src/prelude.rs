@@ -1,12 +1,13 @@
 pub use crate::{
     boxfish::{
-        BitIter, Body, BoxfishRegister, Head, PLAYER_LAYER, Player, Tail,
+        BitIter, Body, BoxfishRegister, CurrentPuzzle, Head, PLAYER_LAYER, Player, Tail,
         movement::{
             OnMoved,
             collision::{Collision, collide_with},
             input::{Direction, Travel},
         },
     },
+    puzzle::{Action, Outcome, PuzzleState},
     stage::Collidable,
     stage_manager::{ConstructAquarium, NewGame, StageManager},
 };
@@ -56,4 +57,10 @@ pub enum MacroStates {
     GamePlay,
     /// In this state, a result will be shown.
     GameClear,
+    /// In this state, why a stage couldn't be loaded will be shown,
+    /// and player can skip it or return to the title.
+    StageError,
+    /// In this state, the boxfish took every step the stage allows,
+    /// and player can undo the last move or restart the stage.
+    OutOfMoves,
 }
@@ -0,0 +1,313 @@
+//! Keeping the player's progress over restarts.
+//!
+//! The save file is `save.toml` in the user data directory,
+//! which records cleared stages, the best steps and the most pearls of each
+//! and the play time.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{
+    boxfish::ResultManager,
+    prelude::*,
+    replay::{ReplayPlayback, record_operations},
+    stage_manager::NextStage,
+    user_data::user_data_dir,
+};
+
+pub struct ProgressPlugin;
+
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Progress>()
+            .init_resource::<StageSteps>()
+            .add_systems(PreStartup, load_progress)
+            .add_systems(
+                Update,
+                count_play_time.run_if(in_state(MacroStates::GamePlay)),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    forget_stage_steps.run_if(on_event::<NewGame>),
+                    record_cleared_stage,
+                    count_stage_steps,
+                )
+                    .chain()
+                    // A stage cleared by a replay isn't the player's record
+                    .before(record_operations),
+            )
+            .add_systems(Last, save_on_exit.run_if(on_event::<AppExit>));
+    }
+}
+
+/// The file name of the save file.
+const SAVE_FILE_NAME: &str = "save.toml";
+
+/// The version of the format of the save file.
+///
+/// Increase it on changing [SaveData], then convert
+/// older ones in [SaveData::from_toml].
+const SAVE_VERSION: i64 = 1;
+
+/// The best record of a cleared stage.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StageRecord {
+    pub index: usize,
+    pub best_steps: u32,
+    /// The most pearls picked up in a clear.
+    #[serde(default)]
+    pub most_pearls: u32,
+}
+
+/// Cleared stages of a stage pack.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PackProgress {
+    /// The title of the stage pack.
+    pub title: String,
+    pub stages: Vec<StageRecord>,
+}
+
+/// The contents of the save file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: i64,
+    /// Seconds spent in gameplay in total.
+    pub play_time: f64,
+    pub packs: Vec<PackProgress>,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            play_time: 0.,
+            packs: Vec::new(),
+        }
+    }
+}
+
+impl SaveData {
+    /// Reading a save file, converting it from older formats if needed.
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let table = toml::from_str::<toml::Table>(source).map_err(|e| e.message().to_string())?;
+        let version = table
+            .get("version")
+            .and_then(|v| v.as_integer())
+            .ok_or("the save file has no version")?;
+        match version {
+            // Converting an older table into the next version goes here
+            SAVE_VERSION => toml::Value::Table(table)
+                .try_into::<SaveData>()
+                .map_err(|e| e.message().to_string()),
+            _ => Err(format!("unknown version {} of the save file", version)),
+        }
+    }
+
+    /// Getting the records of a stage pack.
+    pub fn pack(&self, title: &str) -> Option<&PackProgress> {
+        self.packs.iter().find(|p| p.title == title)
+    }
+
+    /// Getting the best steps of a stage, or None if it's never cleared.
+    pub fn best_steps(&self, title: &str, index: usize) -> Option<u32> {
+        self.pack(title)?
+            .stages
+            .iter()
+            .find(|s| s.index == index)
+            .map(|s| s.best_steps)
+    }
+
+    /// Getting the most pearls picked up in a stage, or None if it's never cleared.
+    pub fn most_pearls(&self, title: &str, index: usize) -> Option<u32> {
+        self.pack(title)?
+            .stages
+            .iter()
+            .find(|s| s.index == index)
+            .map(|s| s.most_pearls)
+    }
+
+    /// Recording a stage cleared with steps and pearls, keeping the best ones.
+    pub fn record_clear(&mut self, title: &str, index: usize, steps: u32, pearls: u32) {
+        let pack = match self.packs.iter().position(|p| p.title == title) {
+            Some(i) => &mut self.packs[i],
+            None => {
+                self.packs.push(PackProgress {
+                    title: title.to_string(),
+                    stages: Vec::new(),
+                });
+                self.packs.last_mut().unwrap()
+            }
+        };
+        match pack.stages.iter_mut().find(|s| s.index == index) {
+            Some(record) => {
+                record.best_steps = record.best_steps.min(steps);
+                record.most_pearls = record.most_pearls.max(pearls);
+            }
+            None => {
+                pack.stages.push(StageRecord {
+                    index,
+                    best_steps: steps,
+                    most_pearls: pearls,
+                });
+                pack.stages.sort_by_key(|s| s.index);
+            }
+        }
+    }
+
+    /// Getting the first stage which isn't cleared yet,
+    /// or the first stage when every stage was cleared.
+    pub fn first_uncleared(&self, title: &str, stage_count: usize) -> usize {
+        (0..stage_count)
+            .find(|i| self.best_steps(title, *i).is_none())
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+/// The progress of the player, which is saved on a stage cleared or on exit.
+pub struct Progress {
+    #[deref]
+    pub save: SaveData,
+    /// Whether an unreadable save file is still in the way,
+    /// so that saving would overwrite it.
+    read_only: bool,
+}
+
+#[derive(Resource, Default)]
+/// Steps taken and pearls picked up before the current stage started,
+/// for counting those in it.
+pub struct StageSteps {
+    /// The pack and the index of the current stage.
+    stage: Option<(usize, usize)>,
+    steps_at_start: u32,
+    pearls_at_start: u32,
+}
+
+fn save_path() -> Option<PathBuf> {
+    Some(user_data_dir()?.join(SAVE_FILE_NAME))
+}
+
+/// Writing the progress into the save file.
+fn save_progress(progress: &Progress) {
+    if progress.read_only {
+        return;
+    }
+    let Some(path) = save_path() else {
+        return;
+    };
+    let result = toml::to_string(&progress.save)
+        .map_err(|e| e.to_string())
+        .and_then(|source| {
+            if let Some(directory) = path.parent() {
+                std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, source).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!(
+            "The progress couldn't be saved to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Reading the save file, if it exists.
+pub fn load_progress(mut progress: ResMut<Progress>) {
+    let Some(path) = save_path() else {
+        return;
+    };
+    let Ok(source) = std::fs::read_to_string(&path) else {
+        return;
+    };
+    let error = match SaveData::from_toml(&source) {
+        Ok(save) => {
+            progress.save = save;
+            return;
+        }
+        Err(e) => e,
+    };
+    // Keeping the unreadable file, e.g. from a newer version, instead of overwriting it
+    let backup = path.with_extension("toml.bak");
+    match std::fs::rename(&path, &backup) {
+        Ok(()) => warn!(
+            "The save file {} is ignored and moved to {}: {}",
+            path.display(),
+            backup.display(),
+            error
+        ),
+        Err(e) => {
+            progress.read_only = true;
+            warn!(
+                "The save file {} is ignored, and the progress won't be saved \
+                 since it couldn't be moved to {} ({}): {}",
+                path.display(),
+                backup.display(),
+                e,
+                error
+            );
+        }
+    }
+}
+
+pub fn count_play_time(time: Res<Time<Real>>, mut progress: ResMut<Progress>) {
+    progress.play_time += time.delta_secs_f64();
+}
+
+/// Counting steps in a stage from zero again on a new game.
+pub fn forget_stage_steps(mut stage_steps: ResMut<StageSteps>) {
+    stage_steps.stage = None;
+}
+
+/// Recording the best steps and the most pearls of a stage,
+/// then saving them on the stage cleared.
+pub fn record_cleared_stage(
+    mut progress: ResMut<Progress>,
+    mut stage_steps: ResMut<StageSteps>,
+    stage_manager: Res<StageManager>,
+    result_manager: Res<ResultManager>,
+    playback: Res<ReplayPlayback>,
+    puzzle: Res<CurrentPuzzle>,
+    mut next_stage: EventReader<NextStage>,
+) {
+    // A skipped stage was never constructed, so it isn't recorded
+    if next_stage.read().count() > 0
+        && puzzle.reached_goal()
+        && !playback.is_replaying()
+        && let Some((pack, index)) = stage_steps.stage.take()
+        && let Some(pack) = stage_manager.packs.get(pack)
+    {
+        let steps = result_manager
+            .steps
+            .saturating_sub(stage_steps.steps_at_start);
+        let pearls = result_manager
+            .pearls
+            .saturating_sub(stage_steps.pearls_at_start);
+        progress.record_clear(&pack.title, index, steps, pearls);
+        save_progress(&progress);
+    }
+}
+
+/// Starting to count steps and pearls of a stage on it constructed.
+pub fn count_stage_steps(
+    mut stage_steps: ResMut<StageSteps>,
+    stage_manager: Res<StageManager>,
+    result_manager: Res<ResultManager>,
+    mut construct_aquarium: EventReader<ConstructAquarium>,
+) {
+    for _ in construct_aquarium.read() {
+        let stage = Some((stage_manager.pack, stage_manager.index));
+        // Resetting the stage keeps counting
+        if stage_steps.stage != stage {
+            stage_steps.stage = stage;
+            stage_steps.steps_at_start = result_manager.steps;
+            stage_steps.pearls_at_start = result_manager.pearls;
+        }
+    }
+}
+
+pub fn save_on_exit(progress: Res<Progress>) {
+    save_progress(&progress);
+}
@@ -0,0 +1,1106 @@
+//! The rules of the aquarium, without any entity or system.
+//!
+//! Bevy's systems only read a [PuzzleState] to render and animate it,
+//! and feed the player's operations into [PuzzleState::play].
+
+mod layout;
+pub mod solver;
+
+pub use layout::{AquariumLayout, Clock, Gate};
+
+use crate::{
+    prelude::*,
+    stage::{LogiKind, RegisterOp},
+};
+use bevy::math::IVec2;
+use itertools::Itertools;
+use std::{borrow::Cow, sync::Arc};
+
+/// An operation of the player.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Moving the head with the rest of the boxfish.
+    Move(Travel),
+    /// Lining the bits up behind the head.
+    Expand,
+    /// Packing the bits next to the head again.
+    Shrink,
+    /// Controlling the next boxfish instead.
+    Switch,
+}
+
+/// What happened as the result of an [Action].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// The action was carried out.
+    Moved,
+    /// The action was impossible, so nothing changed.
+    Blocked,
+    /// Some bits didn't correspond to equal gates on the way,
+    /// so the boxfish was bounced back. Holds the coords of those gates.
+    BouncedByEqualGate { gates: Vec<IVec2> },
+    /// The action was carried out, and the boxfish reached a goal.
+    Goal,
+    /// An enemy fish touched a boxfish, so nothing changed.
+    /// Holds where those fish would have been.
+    Caught { enemies: Vec<IVec2> },
+}
+
+/// A boxfish which isn't controlled now.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Boxfish {
+    head: IVec2,
+    facing: IVec2,
+    is_expanding: bool,
+    overflow: bool,
+    bits: Vec<bool>,
+    bit_histories: Vec<Vec<bool>>,
+}
+
+impl Boxfish {
+    fn new(origin: IVec2, bits: &[bool]) -> Self {
+        Self {
+            head: origin,
+            facing: IVec2::X,
+            is_expanding: false,
+            overflow: false,
+            bits: bits.to_vec(),
+            bit_histories: vec![Vec::new(); bits.len()],
+        }
+    }
+    pub fn head(&self) -> IVec2 {
+        self.head
+    }
+    pub fn facing(&self) -> IVec2 {
+        self.facing
+    }
+    pub fn is_expanding(&self) -> bool {
+        self.is_expanding
+    }
+    pub fn bits(&self) -> &[bool] {
+        &self.bits
+    }
+    /// Cells of the head, the bits and the tail in this order,
+    /// where bits share a cell while packed.
+    pub fn body_cells(&self) -> Vec<IVec2> {
+        let tail = if self.is_expanding {
+            self.bits.len() + 1
+        } else {
+            2
+        };
+        (0..=tail)
+            .map(|offset| self.head - self.facing * offset as i32)
+            .collect()
+    }
+    /// Cells occupied by the head and the bits, excluding the tail.
+    pub fn occupied_cells(&self) -> Vec<IVec2> {
+        let mut cells = self.body_cells();
+        cells.pop();
+        cells
+    }
+}
+
+/// A fish which swims as [EnemyPatrol](crate::stage_manager::EnemyPatrol) says.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Enemy {
+    pos: IVec2,
+    /// Which way it swims next, or swam last when it follows a path.
+    heading: IVec2,
+    /// How many ways of the path it has swum, counted again after the last.
+    turn: usize,
+}
+
+impl Enemy {
+    pub fn pos(&self) -> IVec2 {
+        self.pos
+    }
+    pub fn heading(&self) -> IVec2 {
+        self.heading
+    }
+}
+
+/// Everything an action can change, kept for undo and redo.
+#[derive(Clone)]
+struct Snapshot {
+    boxfish: Boxfish,
+    others: Vec<Boxfish>,
+    active: usize,
+    enemies: Vec<Enemy>,
+    steps: u32,
+    pearls: Vec<IVec2>,
+}
+
+/// The whole status of a stage while playing.
+#[derive(Clone, Default)]
+pub struct PuzzleState {
+    layout: Arc<AquariumLayout>,
+    head: IVec2,
+    /// Where the head faces as a unit vector, the body trails behind it.
+    facing: IVec2,
+    /// Whether moving sideways turns the boxfish.
+    turnable: bool,
+    is_expanding: bool,
+    /// Whether the last adder gate carried out of the highest bit.
+    overflow: bool,
+    bits: Vec<bool>,
+    /// Histories used by undo gates, not by Ctrl+Z.
+    bit_histories: Vec<Vec<bool>>,
+    /// Boxfish which aren't controlled now, in the order of the stage.
+    others: Vec<Boxfish>,
+    /// Which boxfish in the order of the stage is controlled now.
+    active: usize,
+    enemies: Vec<Enemy>,
+    /// How many steps the stage allows, if it's limited.
+    max_steps: Option<u32>,
+    /// How many times the boxfish moved, which undo gives back.
+    steps: u32,
+    /// Pearls which aren't picked up yet.
+    pearls: Vec<IVec2>,
+    /// The warp tile which carried the head in the last move, if any.
+    warped_from: Option<IVec2>,
+    /// The states before each action played, the last one is the latest.
+    undo_stack: Vec<Snapshot>,
+    /// The states undone, the last one is the latest undone.
+    redo_stack: Vec<Snapshot>,
+}
+
+impl From<&ConstructAquarium> for PuzzleState {
+    fn from(aquarium: &ConstructAquarium) -> Self {
+        let layout = Arc::new(AquariumLayout::from_aquarium(aquarium));
+        Self {
+            pearls: layout.pearls.clone(),
+            layout,
+            head: aquarium.player_origin,
+            facing: IVec2::X,
+            turnable: aquarium.turnable,
+            is_expanding: false,
+            overflow: false,
+            bits: aquarium.player_defaultbits.clone(),
+            bit_histories: vec![Vec::new(); aquarium.player_defaultbits.len()],
+            others: aquarium
+                .companions
+                .iter()
+                .map(|c| Boxfish::new(c.origin, &c.defaultbits))
+                .collect(),
+            active: 0,
+            enemies: aquarium
+                .enemies
+                .iter()
+                .map(|patrol| Enemy {
+                    pos: patrol.origin,
+                    heading: patrol.heading,
+                    turn: 0,
+                })
+                .collect(),
+            max_steps: aquarium.max_steps,
+            steps: 0,
+            warped_from: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+}
+
+impl PuzzleState {
+    pub fn layout(&self) -> &AquariumLayout {
+        &self.layout
+    }
+    pub fn head(&self) -> IVec2 {
+        self.head
+    }
+    pub fn facing(&self) -> IVec2 {
+        self.facing
+    }
+    pub fn turnable(&self) -> bool {
+        self.turnable
+    }
+    pub fn is_expanding(&self) -> bool {
+        self.is_expanding
+    }
+    pub fn overflow(&self) -> bool {
+        self.overflow
+    }
+    pub fn bits(&self) -> &[bool] {
+        &self.bits
+    }
+    /// Boxfish which aren't controlled now.
+    pub fn others(&self) -> &[Boxfish] {
+        &self.others
+    }
+    pub fn enemies(&self) -> &[Enemy] {
+        &self.enemies
+    }
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+    /// The warp tile which carried the head in the last move, if any.
+    pub fn warped_from(&self) -> Option<IVec2> {
+        self.warped_from
+    }
+    /// Pearls which aren't picked up yet.
+    pub fn pearls(&self) -> &[IVec2] {
+        &self.pearls
+    }
+    /// How many pearls were picked up in the stage.
+    pub fn collected_pearls(&self) -> u32 {
+        (self.layout.pearls.len() - self.pearls.len()) as u32
+    }
+    /// How many more steps the boxfish can take, or None if it's unlimited.
+    pub fn remaining_steps(&self) -> Option<u32> {
+        self.max_steps.map(|max| max.saturating_sub(self.steps))
+    }
+    /// Whether the boxfish can't move anymore before reaching a goal.
+    pub fn out_of_steps(&self) -> bool {
+        self.remaining_steps() == Some(0) && !self.reached_goal()
+    }
+
+    /// Getting where a bit is. Bits are lined up behind the head
+    /// while expanding, and packed next to the head otherwise.
+    pub fn bit_cell(&self, bit_iter: usize) -> IVec2 {
+        let offset = if self.is_expanding { bit_iter + 1 } else { 1 };
+        self.head - self.facing * offset as i32
+    }
+    /// Getting where the tail is.
+    pub fn tail_cell(&self) -> IVec2 {
+        let offset = if self.is_expanding {
+            self.bits.len() + 1
+        } else {
+            2
+        };
+        self.head - self.facing * offset as i32
+    }
+    /// Cells occupied by the head and the bits, excluding the tail.
+    pub fn occupied_cells(&self) -> Vec<IVec2> {
+        std::iter::once(self.head)
+            .chain((0..self.bits.len()).map(|i| self.bit_cell(i)))
+            .unique()
+            .collect()
+    }
+    /// Cells occupied by any part of the boxfish.
+    pub fn body_cells(&self) -> Vec<IVec2> {
+        let mut cells = self.occupied_cells();
+        cells.push(self.tail_cell());
+        cells
+    }
+    /// Getting doors which are closed now.
+    ///
+    /// A door is open while any plate of its group is pressed by the head
+    /// or a bit, and an overflow door is open while the register has overflowed.
+    /// Neither closes on the boxfish.
+    pub fn closed_doors(&self) -> Vec<IVec2> {
+        if self.layout.doors.is_empty() && self.layout.overflow_doors.is_empty() {
+            return Vec::new();
+        }
+        let mut occupied = self.occupied_cells();
+        let mut body = self.body_cells();
+        for other in &self.others {
+            occupied.extend(other.occupied_cells());
+            body.extend(other.body_cells());
+        }
+        let overflow = self.overflow || self.others.iter().any(|other| other.overflow);
+        let overflow_doors = if overflow {
+            &[][..]
+        } else {
+            &self.layout.overflow_doors[..]
+        };
+        self.layout
+            .doors
+            .iter()
+            .filter(|(group, _)| {
+                !self
+                    .layout
+                    .plates
+                    .iter()
+                    .any(|(g, plate)| g == group && occupied.contains(plate))
+            })
+            .map(|(_, door)| door)
+            .chain(overflow_doors)
+            .filter(|door| !body.contains(door))
+            .copied()
+            .collect()
+    }
+    /// Walls with doors closed now, and the other boxfish.
+    fn walls(&self) -> Cow<'_, Collision> {
+        let mut blocking = self.closed_doors();
+        blocking.extend(self.others.iter().flat_map(Boxfish::body_cells));
+        if blocking.is_empty() {
+            Cow::Borrowed(&self.layout.walls)
+        } else {
+            Cow::Owned(self.layout.walls.clone() + Collision::from(blocking))
+        }
+    }
+    /// Does every boxfish touch a goal which its register fits?
+    pub fn reached_goal(&self) -> bool {
+        let reached = |occupied: Vec<IVec2>, bits: &[bool]| {
+            occupied
+                .iter()
+                .any(|cell| self.layout.goals.contains(cell) && self.goal_accepts(*cell, bits))
+        };
+        reached(self.occupied_cells(), &self.bits)
+            && self
+                .others
+                .iter()
+                .all(|other| reached(other.occupied_cells(), &other.bits))
+    }
+    /// Does the register fit the pattern of a goal? A goal without any pattern
+    /// accepts every register.
+    pub fn fits_goal(&self, goal: IVec2) -> bool {
+        self.goal_accepts(goal, &self.bits)
+    }
+    fn goal_accepts(&self, goal: IVec2, bits: &[bool]) -> bool {
+        self.layout
+            .goal_patterns
+            .iter()
+            .filter(|(at, _)| *at == goal)
+            .all(|(_, pattern)| pattern == bits)
+    }
+    /// Goals which occupied cells touch, but the register doesn't fit.
+    pub fn mismatched_goals(&self) -> Vec<IVec2> {
+        self.occupied_cells()
+            .into_iter()
+            .filter(|cell| self.layout.goals.contains(cell) && !self.fits_goal(*cell))
+            .collect()
+    }
+    /// Getting how far a wall is when the boxfish tries to expand,
+    /// or None when it can expand fully.
+    pub fn expansion_reach(&self) -> Option<usize> {
+        self.walls()
+            .collide_at(
+                &self.head,
+                &Travel::from_ivec2(-self.facing * ((self.bits.len() as i32) + 1)),
+            )
+            .map(|at| (self.head - at).abs().element_sum() as usize)
+    }
+
+    /// Applying an action to the state, remembering the state before it
+    /// when anything changed, so that it can be undone.
+    pub fn play(&mut self, action: Action) -> Outcome {
+        let before = self.snapshot();
+        let outcome = self.step(action);
+        if matches!(outcome, Outcome::Moved | Outcome::Goal) {
+            self.undo_stack.push(before);
+            self.redo_stack.clear();
+        }
+        outcome
+    }
+
+    /// Applying an action to the state without remembering anything.
+    pub fn step(&mut self, action: Action) -> Outcome {
+        // Enemies need the state before it, in case they catch a boxfish
+        let before = (!self.enemies.is_empty()).then(|| (self.snapshot(), self.every_body_cell()));
+        let outcome = match action {
+            Action::Move(_) if self.remaining_steps() == Some(0) => Outcome::Blocked,
+            Action::Move(travel) => {
+                let outcome = self.move_boxfish(&travel);
+                if outcome == Outcome::Moved {
+                    self.warp();
+                    self.drift();
+                    self.swim_enemies();
+                    // Clock gates tick after the whole turn, drifting included
+                    self.steps += 1;
+                }
+                outcome
+            }
+            Action::Expand => {
+                if self.is_expanding || self.expansion_reach().is_some() {
+                    Outcome::Blocked
+                } else {
+                    self.is_expanding = true;
+                    Outcome::Moved
+                }
+            }
+            Action::Shrink => {
+                if self.is_expanding {
+                    self.is_expanding = false;
+                    Outcome::Moved
+                } else {
+                    Outcome::Blocked
+                }
+            }
+            Action::Switch => {
+                if self.others.is_empty() {
+                    Outcome::Blocked
+                } else {
+                    self.switch_boxfish();
+                    Outcome::Moved
+                }
+            }
+        };
+        if outcome == Outcome::Moved
+            && let Some((snapshot, body_before)) = before
+        {
+            let catching = self.catching_enemies(&snapshot.enemies, &body_before);
+            if !catching.is_empty() {
+                self.restore(snapshot);
+                return Outcome::Caught { enemies: catching };
+            }
+        }
+        if outcome == Outcome::Moved {
+            // Expanding and warping may reach pearls without sliding
+            self.pick_up_pearls();
+        }
+        if outcome == Outcome::Moved && self.reached_goal() {
+            Outcome::Goal
+        } else {
+            outcome
+        }
+    }
+
+    /// Cells of every part of every boxfish.
+    fn every_body_cell(&self) -> Vec<IVec2> {
+        let mut cells = self.body_cells();
+        cells.extend(self.others.iter().flat_map(Boxfish::body_cells));
+        cells
+    }
+
+    /// Letting every enemy swim a tile.
+    ///
+    /// One following a path swims regardless of anything, and the other one
+    /// turns back at walls, gates and closed doors, or stays when it can't.
+    fn swim_enemies(&mut self) {
+        let closed = self.closed_doors();
+        let layout = &self.layout;
+        let is_blocked = |cell: IVec2| {
+            layout.walls.contains(&cell)
+                || layout.semiwalls.contains(&cell)
+                || closed.contains(&cell)
+        };
+        for (enemy, patrol) in self.enemies.iter_mut().zip(&layout.enemies) {
+            if let Some(way) = patrol.path.get(enemy.turn % patrol.path.len().max(1)) {
+                enemy.pos += *way;
+                enemy.heading = *way;
+                enemy.turn = (enemy.turn + 1) % patrol.path.len();
+                continue;
+            }
+            if is_blocked(enemy.pos + enemy.heading) {
+                enemy.heading = -enemy.heading;
+            }
+            if !is_blocked(enemy.pos + enemy.heading) {
+                enemy.pos += enemy.heading;
+            }
+        }
+    }
+
+    /// Getting where enemies touching any boxfish are.
+    ///
+    /// An enemy and a boxfish also touch when they swapped their cells.
+    fn catching_enemies(&self, enemies_before: &[Enemy], body_before: &[IVec2]) -> Vec<IVec2> {
+        let body = self.every_body_cell();
+        self.enemies
+            .iter()
+            .zip(enemies_before)
+            .filter(|(now, before)| {
+                body.contains(&now.pos)
+                    || (body.contains(&before.pos) && body_before.contains(&now.pos))
+            })
+            .map(|(now, _)| now.pos)
+            .collect()
+    }
+
+    fn move_boxfish(&mut self, travel: &Travel) -> Outcome {
+        let toward = travel.into_ivec2().signum();
+        if self.turnable && toward != self.facing && toward != -self.facing {
+            return self.turn_boxfish(travel);
+        }
+        self.slide_boxfish(travel)
+    }
+
+    /// Moving the boxfish without turning.
+    fn slide_boxfish(&mut self, travel: &Travel) -> Outcome {
+        // Gates are passable only while expanding
+        let walls = self.walls();
+        let was_collided = self.body_cells().iter().any(|cell| {
+            walls.do_collide(cell, travel)
+                || (!self.is_expanding && self.layout.semiwalls.do_collide(cell, travel))
+        });
+        if was_collided {
+            return Outcome::Blocked;
+        }
+        // Gates which each bit passes through on the way
+        let passed: Vec<Vec<Gate>> = (0..self.bits.len())
+            .map(|i| {
+                let from = self.bit_cell(i);
+                self.layout
+                    .gates
+                    .iter()
+                    .filter(|gate| collide_with(&from, travel, &gate.pos))
+                    // Clock gates work as they're shown in this turn
+                    .map(|gate| Gate {
+                        boolean: gate.boolean_at(self.steps),
+                        ..*gate
+                    })
+                    .collect()
+            })
+            .collect();
+        // Equal gates bounce the boxfish back before any other gate works
+        let incorresponded: Vec<IVec2> = passed
+            .iter()
+            .zip(&self.bits)
+            .flat_map(|(gates, bit)| {
+                gates
+                    .iter()
+                    .filter(|g| g.logikind == LogiKind::Equal && g.boolean != *bit)
+                    .map(|g| g.pos)
+            })
+            .collect();
+        if !incorresponded.is_empty() {
+            return Outcome::BouncedByEqualGate {
+                gates: incorresponded,
+            };
+        }
+        for ((gates, bit), history) in passed
+            .iter()
+            .zip(&mut self.bits)
+            .zip(&mut self.bit_histories)
+        {
+            for gate in gates {
+                process_gate_effect_for_each_bit(gate, bit, history);
+            }
+        }
+        // Adder gates work on the whole register, since a carry goes through bits
+        let addend: Vec<Option<bool>> = passed
+            .iter()
+            .map(|gates| {
+                gates
+                    .iter()
+                    .find(|g| g.logikind == LogiKind::Add)
+                    .map(|g| g.boolean)
+            })
+            .collect();
+        if addend.iter().any(Option::is_some) {
+            let addend: Vec<bool> = addend.into_iter().map(|a| a.unwrap_or(false)).collect();
+            self.overflow = process_adder_gate(&addend, &mut self.bits, &mut self.bit_histories);
+        }
+        self.head += travel.into_ivec2();
+        self.apply_register_gate();
+        // A current carries the boxfish over pearls on the way
+        self.pick_up_pearls();
+        Outcome::Moved
+    }
+
+    /// Picking up pearls under the head or the bits.
+    fn pick_up_pearls(&mut self) {
+        if self.pearls.is_empty() {
+            return;
+        }
+        let occupied = self.occupied_cells();
+        self.pearls.retain(|pearl| !occupied.contains(pearl));
+    }
+
+    /// Moving the head sideways, then letting the body trail behind it.
+    ///
+    /// Bits jump to their new cells without passing any gate,
+    /// so they can't land on any part of gates.
+    fn turn_boxfish(&mut self, travel: &Travel) -> Outcome {
+        let head_collided = self.walls().do_collide(&self.head, travel)
+            || (!self.is_expanding && self.layout.semiwalls.do_collide(&self.head, travel));
+        if head_collided {
+            return Outcome::Blocked;
+        }
+        let walls = self.walls().into_owned();
+        let before = (self.head, self.facing);
+        self.head += travel.into_ivec2();
+        self.facing = travel.into_ivec2().signum();
+        if self.is_body_stuck(&walls) {
+            (self.head, self.facing) = before;
+            return Outcome::Blocked;
+        }
+        self.apply_register_gate();
+        Outcome::Moved
+    }
+
+    /// Carrying the boxfish to the partner when the head is on a warp tile.
+    ///
+    /// The boxfish stays when its body doesn't fit around the partner.
+    fn warp(&mut self) {
+        self.warped_from = None;
+        let Some(partner) = self.layout.warp_partner(self.head) else {
+            return;
+        };
+        let walls = self.walls().into_owned();
+        let before = self.head;
+        self.head = partner;
+        if self.is_body_stuck(&walls) {
+            self.head = before;
+        } else {
+            self.warped_from = Some(before);
+        }
+    }
+
+    /// Applying the register gate under the head, if any.
+    ///
+    /// A push gate does nothing when the longer body doesn't fit behind the head.
+    fn apply_register_gate(&mut self) {
+        let Some((_, op)) = self
+            .layout
+            .register_gates
+            .iter()
+            .find(|(pos, _)| *pos == self.head)
+        else {
+            return;
+        };
+        if matches!(op, RegisterOp::Push(_)) && self.is_expanding {
+            let new_tail = self.tail_cell() - self.facing;
+            if self.walls().contains(&new_tail) || self.layout.semiwalls.contains(&new_tail) {
+                return;
+            }
+        }
+        process_register_gate(*op, &mut self.bits, &mut self.bit_histories);
+    }
+
+    /// Letting the current under the head carry the boxfish until it's blocked.
+    ///
+    /// Gates on the way work as usual, and equal gates stop it.
+    /// Warps and currents it's carried onto do nothing,
+    /// and enemies catch it only where it stops.
+    fn drift(&mut self) {
+        let Some(flow) = self.layout.current_at(self.head) else {
+            return;
+        };
+        let travel = Travel::from_ivec2(flow);
+        while self.slide_boxfish(&travel) == Outcome::Moved {}
+    }
+
+    /// Does any part of the boxfish behind the head lie on walls or gates?
+    ///
+    /// It's used when the body was put somewhere without passing the way.
+    /// Walls are the ones before it was put, so that doors don't close on it.
+    fn is_body_stuck(&self, walls: &Collision) -> bool {
+        self.body_cells()
+            .iter()
+            .skip(1)
+            .any(|cell| walls.contains(cell) || self.layout.semiwalls.contains(cell))
+    }
+
+    /// Controlling the next boxfish in the order of the stage,
+    /// the first one comes after the last one.
+    fn switch_boxfish(&mut self) {
+        let next = (self.active + 1) % (self.others.len() + 1);
+        // Putting the current one back in order, then taking the next one out
+        let current = self.boxfish();
+        self.others.insert(self.active, current);
+        let next_boxfish = self.others.remove(next);
+        self.set_boxfish(next_boxfish);
+        self.active = next;
+    }
+    /// The boxfish controlled now.
+    fn boxfish(&self) -> Boxfish {
+        Boxfish {
+            head: self.head,
+            facing: self.facing,
+            is_expanding: self.is_expanding,
+            overflow: self.overflow,
+            bits: self.bits.clone(),
+            bit_histories: self.bit_histories.clone(),
+        }
+    }
+    fn set_boxfish(&mut self, boxfish: Boxfish) {
+        self.head = boxfish.head;
+        self.facing = boxfish.facing;
+        self.is_expanding = boxfish.is_expanding;
+        self.overflow = boxfish.overflow;
+        self.bits = boxfish.bits;
+        self.bit_histories = boxfish.bit_histories;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            boxfish: self.boxfish(),
+            others: self.others.clone(),
+            active: self.active,
+            enemies: self.enemies.clone(),
+            steps: self.steps,
+            pearls: self.pearls.clone(),
+        }
+    }
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.set_boxfish(snapshot.boxfish);
+        self.others = snapshot.others;
+        self.active = snapshot.active;
+        self.enemies = snapshot.enemies;
+        self.steps = snapshot.steps;
+        self.pearls = snapshot.pearls;
+    }
+    /// Forgetting every state remembered for undo and redo.
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Restoring the state before the last played action.
+    ///
+    /// Returns false when there's nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(last) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.redo_stack.push(self.snapshot());
+        self.restore(last);
+        true
+    }
+    /// Playing the last undone action again.
+    ///
+    /// Returns false when there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(next) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.undo_stack.push(self.snapshot());
+        self.restore(next);
+        true
+    }
+}
+
+/// Processing a gate's effect for a bit.
+///
+/// And gate(&) : Appling AND operation for the bit with passed gate's register.
+///
+/// Or gate(|) : Appling OR operation for the bit with passed gate's register.
+///
+/// Xor gate(|) : Appling XOR operation for the bit with passed gate's register.
+///
+/// Nand, Nor and Xnor gate : Appling the negation of AND, OR and XOR operation.
+///
+/// Not gate(!) : Revert the bit if passed gate's register was 1.
+///
+/// Undo gate(↻) : Restorate before bit pattern from history.
+///
+/// Equal gate(=) : Impassable when the bit and gate's register isn't same.
+/// It's checked before moving, so it does nothing here.
+///
+/// Adder gate(+) : Adding gate's registers to the whole register.
+/// It works after the other gates, so it does nothing here.
+pub fn process_gate_effect_for_each_bit(gate: &Gate, bit: &mut bool, history: &mut Vec<bool>) {
+    let now = *bit;
+    match gate.logikind {
+        LogiKind::And => {
+            history.push(now);
+            *bit &= gate.boolean;
+        }
+        LogiKind::Or => {
+            history.push(now);
+            *bit |= gate.boolean;
+        }
+        LogiKind::Not => {
+            if gate.boolean {
+                history.push(now);
+                *bit = !*bit
+            }
+        }
+        LogiKind::Xor => {
+            history.push(now);
+            *bit ^= gate.boolean;
+        }
+        LogiKind::Nand => {
+            history.push(now);
+            *bit = !(now & gate.boolean);
+        }
+        LogiKind::Nor => {
+            history.push(now);
+            *bit = !(now | gate.boolean);
+        }
+        LogiKind::Xnor => {
+            history.push(now);
+            *bit = !(now ^ gate.boolean);
+        }
+        LogiKind::Undo => {
+            if let Some(last) = history.pop() {
+                *bit = last;
+            }
+        }
+        LogiKind::Equal | LogiKind::Add => (),
+    }
+}
+
+/// Processing a gate's effect for the whole register.
+///
+/// Every bit remembers its value before it, so an undo gate can restore them.
+/// Pushing and popping change the length instead, which an undo gate can't restore.
+pub fn process_register_gate(op: RegisterOp, bits: &mut Vec<bool>, histories: &mut Vec<Vec<bool>>) {
+    match op {
+        RegisterOp::Push(boolean) => {
+            bits.push(boolean);
+            histories.push(Vec::new());
+        }
+        RegisterOp::Pop => {
+            if bits.len() > 1 {
+                bits.pop();
+                histories.pop();
+            }
+        }
+        RegisterOp::ShiftLeft
+        | RegisterOp::ShiftRight
+        | RegisterOp::RotateLeft
+        | RegisterOp::RotateRight => {
+            for (bit, history) in bits.iter().zip(histories.iter_mut()) {
+                history.push(*bit);
+            }
+            // The lowest bit comes first
+            if matches!(op, RegisterOp::ShiftLeft | RegisterOp::RotateLeft) {
+                bits.rotate_right(1);
+            } else {
+                bits.rotate_left(1);
+            }
+            match op {
+                RegisterOp::ShiftLeft => {
+                    if let Some(lowest) = bits.first_mut() {
+                        *lowest = false;
+                    }
+                }
+                RegisterOp::ShiftRight => {
+                    if let Some(highest) = bits.last_mut() {
+                        *highest = false;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Adding a value to the register, both as unsigned integers from the lowest bit.
+///
+/// Every bit remembers its value before it, so an undo gate can restore them.
+/// Returns whether it carried out of the highest bit.
+pub fn process_adder_gate(addend: &[bool], bits: &mut [bool], histories: &mut [Vec<bool>]) -> bool {
+    let mut carry = false;
+    for ((bit, history), add) in bits.iter_mut().zip(histories.iter_mut()).zip(addend) {
+        history.push(*bit);
+        let sum = *bit as u8 + *add as u8 + carry as u8;
+        *bit = sum % 2 == 1;
+        carry = sum >= 2;
+    }
+    carry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Building a puzzle from a stage written inline, with more fields if any.
+    fn puzzle(content: &str, origin: IVec2, bits: &[bool], extra: &str) -> PuzzleState {
+        let source = format!(
+            "stage_name = \"test\"\ncontent = \"\"\"\n{}\"\"\"\nplayer_origin = [{}, {}]\nplayer_defaultbits = {:?}\n{}",
+            content, origin.x, origin.y, bits, extra
+        );
+        PuzzleState::from(&ConstructAquarium::from_toml(&source).unwrap())
+    }
+
+    fn travel(toward: IVec2) -> Action {
+        Action::Move(Travel::from_ivec2(toward))
+    }
+
+    #[test]
+    fn moving_in_open_water_and_into_a_wall() {
+        let mut state = puzzle("     E\n   W  \n      \n", IVec2::new(2, 1), &[false], "");
+        assert_eq!(state.play(travel(IVec2::X)), Outcome::Blocked);
+        assert_eq!(state.head(), IVec2::new(2, 1));
+        assert_eq!(state.play(travel(IVec2::Y)), Outcome::Moved);
+        assert_eq!(state.head(), IVec2::new(2, 2));
+    }
+
+    #[test]
+    fn reaching_a_goal() {
+        let mut state = puzzle("   E  \n", IVec2::new(2, 0), &[false], "");
+        assert_eq!(state.play(travel(IVec2::X)), Outcome::Goal);
+        assert!(state.reached_goal());
+    }
+
+    #[test]
+    fn bounced_by_an_equal_gate() {
+        let mut state = puzzle(
+            "      \n G10G \n     E\n",
+            IVec2::new(4, 2),
+            &[false, false],
+            "",
+        );
+        assert_eq!(state.play(Action::Expand), Outcome::Moved);
+        // Only the second bit differs from the gate
+        assert_eq!(
+            state.play(travel(IVec2::NEG_Y)),
+            Outcome::BouncedByEqualGate {
+                gates: vec![IVec2::new(2, 1)]
+            }
+        );
+        assert_eq!(state.head(), IVec2::new(4, 2));
+    }
+
+    #[test]
+    fn caught_by_an_enemy() {
+        let mut state = puzzle(
+            "     E\n",
+            IVec2::new(2, 0),
+            &[false],
+            "[[enemies]]\norigin = [4, 0]\nheading = [-1, 0]\n",
+        );
+        assert_eq!(
+            state.play(travel(IVec2::X)),
+            Outcome::Caught {
+                enemies: vec![IVec2::new(3, 0)]
+            }
+        );
+        // The move is taken back, and can't be undone
+        assert_eq!(state.head(), IVec2::new(2, 0));
+        assert_eq!(state.enemies()[0].pos(), IVec2::new(4, 0));
+        assert!(!state.undo());
+    }
+
+    #[test]
+    fn undo_restores_bits_and_histories() {
+        let mut state = puzzle(
+            "      \n N11N \n     E\n",
+            IVec2::new(4, 2),
+            &[false, false],
+            "",
+        );
+        state.play(Action::Expand);
+        assert_eq!(state.play(travel(IVec2::NEG_Y)), Outcome::Moved);
+        assert_eq!(state.bits(), [true, true]);
+        assert_eq!(state.bit_histories, vec![vec![false], vec![false]]);
+
+        assert!(state.undo());
+        assert_eq!(state.head(), IVec2::new(4, 2));
+        assert_eq!(state.bits(), [false, false]);
+        assert_eq!(state.bit_histories, vec![Vec::<bool>::new(), Vec::new()]);
+
+        assert!(state.redo());
+        assert_eq!(state.bits(), [true, true]);
+        assert_eq!(state.bit_histories, vec![vec![false], vec![false]]);
+    }
+
+    #[test]
+    fn adder_gate_carries_into_higher_bits() {
+        // 1 + 1, from the lowest bit
+        let mut state = puzzle(
+            "      \n +01+ \n     E\n",
+            IVec2::new(4, 2),
+            &[true, false],
+            "",
+        );
+        state.play(Action::Expand);
+        assert_eq!(state.play(travel(IVec2::NEG_Y)), Outcome::Moved);
+        assert_eq!(state.bits(), [false, true]);
+        assert!(!state.overflow);
+    }
+
+    #[test]
+    fn overflow_opens_doors_until_undone() {
+        // 3 + 1 carries out of the highest bit
+        let mut state = puzzle(
+            "     C\n +01+ \n     E\n",
+            IVec2::new(4, 2),
+            &[true, true],
+            "",
+        );
+        let door = IVec2::new(5, 2);
+        assert_eq!(state.closed_doors(), [door]);
+        state.play(Action::Expand);
+        assert_eq!(state.play(travel(IVec2::NEG_Y)), Outcome::Moved);
+        assert_eq!(state.bits(), [false, false]);
+        assert!(state.overflow);
+        assert!(state.closed_doors().is_empty());
+
+        assert!(state.undo());
+        assert_eq!(state.bits(), [true, true]);
+        assert!(!state.overflow);
+        assert_eq!(state.closed_doors(), [door]);
+    }
+
+    #[test]
+    fn pushing_and_popping_change_the_length() {
+        let mut state = puzzle("   i-  E\n", IVec2::new(2, 0), &[false], "");
+        state.play(travel(IVec2::X));
+        assert_eq!(state.bits(), [false, true]);
+        state.play(travel(IVec2::X));
+        assert_eq!(state.bits(), [false]);
+    }
+
+    #[test]
+    fn push_blocked_by_a_wall_while_expanding() {
+        let source = "  i   \n     E\n";
+        // The longer tail would be in the outline of the aquarium
+        let mut state = puzzle(source, IVec2::new(2, 0), &[false], "");
+        state.play(Action::Expand);
+        assert_eq!(state.play(travel(IVec2::Y)), Outcome::Moved);
+        assert_eq!(state.bits(), [false]);
+        // Packed bits don't need more room
+        let mut state = puzzle(source, IVec2::new(2, 0), &[false], "");
+        assert_eq!(state.play(travel(IVec2::Y)), Outcome::Moved);
+        assert_eq!(state.bits(), [false, true]);
+    }
+
+    #[test]
+    fn undo_restores_a_popped_bit() {
+        let mut state = puzzle("   [-  E\n", IVec2::new(2, 0), &[true, false], "");
+        state.play(travel(IVec2::X));
+        assert_eq!(state.bits(), [false, true]);
+        assert_eq!(state.bit_histories, vec![vec![true], vec![false]]);
+        state.play(travel(IVec2::X));
+        assert_eq!(state.bits(), [false]);
+        assert_eq!(state.bit_histories, vec![vec![true]]);
+
+        assert!(state.undo());
+        assert_eq!(state.bits(), [false, true]);
+        assert_eq!(state.bit_histories, vec![vec![true], vec![false]]);
+    }
+
+    #[test]
+    fn clock_gate_flips_with_steps() {
+        let mut state = puzzle(
+            "      \n O01O \n     E\n",
+            IVec2::new(4, 2),
+            &[false, false],
+            "[[clocks]]\nat = [1, 1]\nperiod = 2\nphase = 1\n",
+        );
+        let shown = |state: &PuzzleState| {
+            [2, 3].map(|x| {
+                let gate = state.layout().gates.iter().find(|g| g.pos.x == x).unwrap();
+                gate.boolean_at(state.steps())
+            })
+        };
+        // A step has already passed in the first period
+        assert_eq!(shown(&state), [false, true]);
+        state.play(travel(IVec2::X));
+        assert_eq!(shown(&state), [true, false]);
+        state.play(travel(IVec2::NEG_X));
+        assert_eq!(shown(&state), [true, false]);
+        // The bits get what's shown when they pass
+        state.play(Action::Expand);
+        assert_eq!(state.play(travel(IVec2::NEG_Y)), Outcome::Moved);
+        assert_eq!(state.bits(), [false, true]);
+        assert_eq!(shown(&state), [false, true]);
+
+        // Undo turns the clock back
+        state.undo();
+        state.undo();
+        assert_eq!(state.bits(), [false, false]);
+        assert_eq!(shown(&state), [true, false]);
+        state.undo();
+        state.undo();
+        assert_eq!(state.steps(), 0);
+        assert_eq!(shown(&state), [false, true]);
+    }
+
+    #[test]
+    fn drifting_only_stops_at_walls() {
+        let mut state = puzzle(
+            "2         E\n   >2<     \n",
+            IVec2::new(2, 0),
+            &[false],
+            "[[enemies]]\norigin = [7, 0]\n",
+        );
+        // Passing a warp, a current the other way and an enemy on the way
+        assert_eq!(state.play(travel(IVec2::X)), Outcome::Moved);
+        assert_eq!(state.head(), IVec2::new(10, 0));
+    }
+
+    #[test]
+    fn warping_to_the_partner() {
+        let mut state = puzzle("    3 E\n   3   \n", IVec2::new(2, 0), &[false], "");
+        state.play(travel(IVec2::X));
+        assert_eq!(state.head(), IVec2::new(4, 1));
+        assert_eq!(state.warped_from(), Some(IVec2::new(3, 0)));
+        state.play(travel(IVec2::X));
+        assert_eq!(state.warped_from(), None);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    prelude::*,
+    stage::{
+        LogiKind, RegisterOp, Tile,
+        construction::{aquarium_size, interprint_aquarium, outline_tiles},
+    },
+    stage_manager::EnemyPatrol,
+};
+use bevy::math::IVec2;
+
+/// A boolean stored in a logical gate.
+#[derive(Clone, Copy, Debug)]
+pub struct Gate {
+    pub pos: IVec2,
+    /// The boolean written in the stage, before any clock flips it.
+    pub boolean: bool,
+    pub logikind: LogiKind,
+    pub clock: Option<Clock>,
+}
+
+impl Gate {
+    /// Getting the boolean after the boxfish took some steps.
+    pub fn boolean_at(&self, steps: u32) -> bool {
+        match self.clock {
+            Some(clock) => self.boolean ^ ((steps + clock.phase) / clock.period % 2 == 1),
+            None => self.boolean,
+        }
+    }
+}
+
+/// How often the bits of a clock gate flip, in steps.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub period: u32,
+    /// Steps which have already passed in the first period.
+    pub phase: u32,
+}
+
+/// Tiles of a stage which never change while playing.
+#[derive(Default)]
+pub struct AquariumLayout {
+    /// Walls and the outline of the aquarium.
+    pub walls: Collision,
+    /// Both ends and bits of logical gates.
+    /// Those are passable only while expanding.
+    pub semiwalls: Collision,
+    pub gates: Vec<Gate>,
+    pub goals: Vec<IVec2>,
+    /// Patterns which the register needs to be on goals.
+    pub goal_patterns: Vec<(IVec2, Vec<bool>)>,
+    /// Pairs of warp tiles, which carry the boxfish to each other.
+    pub warps: Vec<(IVec2, IVec2)>,
+    /// Pressure plates with their groups.
+    pub plates: Vec<(char, IVec2)>,
+    /// Doors with their groups, which are closed unless a plate is pressed.
+    pub doors: Vec<(char, IVec2)>,
+    /// Doors which are closed unless the register has overflowed.
+    pub overflow_doors: Vec<IVec2>,
+    /// Water currents with where they flow as unit vectors.
+    pub currents: Vec<(IVec2, IVec2)>,
+    /// Gates which work on the whole register.
+    pub register_gates: Vec<(IVec2, RegisterOp)>,
+    /// How enemy fish swim.
+    pub enemies: Vec<EnemyPatrol>,
+    /// Pearls which aren't picked up yet when the stage starts.
+    pub pearls: Vec<IVec2>,
+}
+
+impl AquariumLayout {
+    /// Interprinting the content of a stage.
+    pub fn from_content(content: &str) -> Self {
+        let mut walls = Vec::new();
+        let mut semiwalls = Vec::new();
+        let mut gates = Vec::new();
+        let mut goals = Vec::new();
+        let mut warps = Vec::new();
+        let mut unpaired_warps: Vec<(u32, IVec2)> = Vec::new();
+        let mut plates = Vec::new();
+        let mut doors = Vec::new();
+        let mut overflow_doors = Vec::new();
+        let mut currents = Vec::new();
+        let mut register_gates = Vec::new();
+        let mut pearls = Vec::new();
+        for (pos, tile) in interprint_aquarium(content).0 {
+            match tile {
+                Tile::Wall => walls.push(pos),
+                Tile::Goal => goals.push(pos),
+                Tile::GateEnd { .. } => semiwalls.push(pos),
+                Tile::GateBit { logikind, boolean } => {
+                    semiwalls.push(pos);
+                    gates.push(Gate {
+                        pos,
+                        boolean,
+                        logikind,
+                        clock: None,
+                    });
+                }
+                Tile::Warp { pair } => match unpaired_warps.iter().position(|(p, _)| *p == pair) {
+                    Some(i) => warps.push((unpaired_warps.swap_remove(i).1, pos)),
+                    None => unpaired_warps.push((pair, pos)),
+                },
+                Tile::Plate { group } => plates.push((group, pos)),
+                Tile::Door { group } => doors.push((group, pos)),
+                Tile::OverflowDoor => overflow_doors.push(pos),
+                Tile::Current { flow } => currents.push((pos, flow)),
+                Tile::RegisterGate { op } => register_gates.push((pos, op)),
+                Tile::Pearl => pearls.push(pos),
+            }
+        }
+        walls.extend(
+            outline_tiles(aquarium_size(content))
+                .into_iter()
+                .map(|(_, pos)| pos),
+        );
+        Self {
+            walls: Collision::from(walls),
+            semiwalls: Collision::from(semiwalls),
+            gates,
+            goals,
+            goal_patterns: Vec::new(),
+            warps,
+            plates,
+            doors,
+            overflow_doors,
+            currents,
+            register_gates,
+            enemies: Vec::new(),
+            pearls,
+        }
+    }
+
+    /// Interprinting the content of a stage with the patterns of goals,
+    /// enemies and clocks.
+    pub fn from_aquarium(aquarium: &ConstructAquarium) -> Self {
+        let mut layout = Self {
+            goal_patterns: aquarium
+                .goal_patterns
+                .iter()
+                .map(|p| (p.at, p.bits.clone()))
+                .collect(),
+            enemies: aquarium.enemies.clone(),
+            ..Self::from_content(&aquarium.content)
+        };
+        let tiles = interprint_aquarium(&aquarium.content).0;
+        for clock in &aquarium.clocks {
+            let Some(direction) = tiles.iter().find_map(|(pos, tile)| match tile {
+                Tile::GateEnd {
+                    is_head: false,
+                    direction,
+                    ..
+                } if *pos == clock.at => Some(*direction),
+                _ => None,
+            }) else {
+                continue;
+            };
+            // Bits follow the tail until the head, which isn't a gate's bit
+            let toward = match direction {
+                Direction::X => IVec2::X,
+                Direction::Y => IVec2::NEG_Y,
+            };
+            let mut pos = clock.at + toward;
+            while let Some(gate) = layout.gates.iter_mut().find(|g| g.pos == pos) {
+                gate.clock = Some(Clock {
+                    period: clock.period,
+                    phase: clock.phase,
+                });
+                pos += toward;
+            }
+        }
+        layout
+    }
+
+    /// How many steps it takes for every clock gate to be back as it started.
+    pub fn clock_cycle(&self) -> u32 {
+        let gcd = |mut a: u32, mut b: u32| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        self.gates
+            .iter()
+            .filter_map(|g| g.clock)
+            .fold(1, |cycle, clock| {
+                let flips_back = clock.period * 2;
+                cycle / gcd(cycle, flips_back) * flips_back
+            })
+    }
+
+    /// Getting where the current on a cell flows, if any.
+    pub fn current_at(&self, pos: IVec2) -> Option<IVec2> {
+        self.currents
+            .iter()
+            .find(|(p, _)| *p == pos)
+            .map(|(_, flow)| *flow)
+    }
+
+    /// Getting where a warp tile carries the boxfish to.
+    pub fn warp_partner(&self, pos: IVec2) -> Option<IVec2> {
+        self.warps.iter().find_map(|(a, b)| match pos {
+            p if p == *a => Some(*b),
+            p if p == *b => Some(*a),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,330 @@
+//! Searching the shortest way to a goal over every reachable [PuzzleState].
+
+use super::{Action, Boxfish, Enemy, Outcome, PuzzleState};
+use crate::{
+    prelude::*,
+    stage::{LogiKind, RegisterOp},
+};
+use bevy::math::IVec2;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+/// How many states will be searched at most by default.
+pub const DEFAULT_STATE_LIMIT: usize = 1_000_000;
+
+/// The result of [solve].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Solution {
+    /// The shortest sequence of actions which reaches a goal.
+    Found(Vec<Action>),
+    /// Every reachable state was searched, and none of them reached a goal.
+    Unsolvable,
+    /// Too many states to search, so it's unknown whether it's solvable.
+    GaveUp,
+}
+
+impl Solution {
+    /// How many steps the solution takes, counted in the same way as
+    /// [ResultManager](crate::boxfish::ResultManager) (expanding is free).
+    pub fn steps(&self) -> Option<u32> {
+        match self {
+            Solution::Found(actions) => Some(
+                actions
+                    .iter()
+                    .filter(|a| matches!(a, Action::Move(_)))
+                    .count() as u32,
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// The part of a [PuzzleState] which makes a difference for the future.
+///
+/// Histories of bits matter only when the stage has undo gates,
+/// and steps matter only as far as clock gates are told apart.
+#[derive(PartialEq, Eq, Hash)]
+struct StateKey {
+    head: IVec2,
+    facing: IVec2,
+    is_expanding: bool,
+    overflow: bool,
+    bits: Vec<bool>,
+    bit_histories: Option<Vec<Vec<bool>>>,
+    /// The other boxfish, whose histories are emptied unless they matter.
+    others: Vec<Boxfish>,
+    active: usize,
+    enemies: Vec<Enemy>,
+    /// Steps in the cycle of clock gates.
+    clock_turn: u32,
+}
+
+impl StateKey {
+    fn new(state: &PuzzleState, with_histories: bool, clock_cycle: u32) -> Self {
+        Self {
+            head: state.head,
+            facing: state.facing,
+            is_expanding: state.is_expanding,
+            overflow: state.overflow,
+            bits: state.bits.clone(),
+            bit_histories: with_histories.then(|| state.bit_histories.clone()),
+            others: state
+                .others
+                .iter()
+                .map(|other| Boxfish {
+                    bit_histories: if with_histories {
+                        other.bit_histories.clone()
+                    } else {
+                        Vec::new()
+                    },
+                    ..other.clone()
+                })
+                .collect(),
+            active: state.active,
+            enemies: state.enemies.clone(),
+            clock_turn: state.steps % clock_cycle,
+        }
+    }
+}
+
+/// Every action the player can take in a turn.
+fn candidates() -> [Action; 7] {
+    let travel = |direction, amount| Action::Move(Travel { direction, amount });
+    [
+        travel(Direction::Y, 1),
+        travel(Direction::Y, -1),
+        travel(Direction::X, -1),
+        travel(Direction::X, 1),
+        Action::Expand,
+        Action::Shrink,
+        Action::Switch,
+    ]
+}
+
+/// The cost of an action, as (steps, other actions).
+///
+/// Fewer steps are always prior, since only moving counts as a step.
+fn cost_of(action: &Action) -> (u32, u32) {
+    match action {
+        Action::Move(_) => (1, 0),
+        Action::Expand | Action::Shrink | Action::Switch => (0, 1),
+    }
+}
+
+/// The fewest steps needed to put every boxfish on a goal.
+///
+/// Each step moves only one boxfish, so steps of each one are added up.
+fn estimate_steps(state: &PuzzleState) -> u32 {
+    let others = state
+        .others
+        .iter()
+        .map(|other| estimate_steps_of(state, other.head, other.facing, other.bits.len()))
+        .sum::<u32>();
+    estimate_steps_of(state, state.head, state.facing, state.bits.len()) + others
+}
+
+/// The fewest steps needed to put any occupied cell of a boxfish on a goal.
+///
+/// Any bit may be the one reaching the goal, since expanding is free,
+/// and the boxfish may face anywhere when it can turn.
+/// Going through warps or currents may be shorter,
+/// so they're also taken into account.
+fn estimate_steps_of(state: &PuzzleState, head: IVec2, facing: IVec2, length: usize) -> u32 {
+    let direct = estimate_steps_from(state, head, facing, length);
+    // A current may carry the boxfish anywhere after reaching it
+    if !state.layout.currents.is_empty() {
+        let warps = state.layout.warps.iter().flat_map(|(a, b)| [*a, *b]);
+        let currents = state.layout.currents.iter().map(|(pos, _)| *pos);
+        return warps
+            .chain(currents)
+            .map(|cell| (cell - head).abs().element_sum() as u32)
+            .fold(direct, u32::min);
+    }
+    let warps = &state.layout.warps;
+    // Any way through warps enters one of them, then leaves another one
+    let to_warp = warps
+        .iter()
+        .flat_map(|(a, b)| [*a, *b])
+        .map(|warp| (warp - head).abs().element_sum() as u32)
+        .min();
+    let from_warp = warps
+        .iter()
+        .flat_map(|(a, b)| [*a, *b])
+        .map(|warp| estimate_steps_from(state, warp, facing, length))
+        .min();
+    match (to_warp, from_warp) {
+        (Some(to), Some(from)) => direct.min(to + from),
+        _ => direct,
+    }
+}
+
+/// The fewest steps needed to put any occupied cell on a goal,
+/// when the head were at the given cell.
+///
+/// Push gates may lengthen the register on the way,
+/// so the body may reach arbitrarily far when the stage has any.
+fn estimate_steps_from(state: &PuzzleState, head: IVec2, facing: IVec2, length: usize) -> u32 {
+    let can_push = state
+        .layout
+        .register_gates
+        .iter()
+        .any(|(_, op)| matches!(op, RegisterOp::Push(_)));
+    let reach = if can_push {
+        i32::MAX
+    } else {
+        length.max(1) as i32
+    };
+    let facings = if state.turnable {
+        vec![IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+    } else {
+        vec![facing]
+    };
+    // The nearest cell to the head on the line from a goal to the head reaching it
+    let to_line = |goal: IVec2, facing: IVec2| {
+        let offset = head - goal;
+        let along = offset.dot(facing).clamp(0, reach);
+        (offset - facing * along).abs().element_sum() as u32
+    };
+    state
+        .layout
+        .goals
+        .iter()
+        .flat_map(|goal| facings.iter().map(|facing| to_line(*goal, *facing)))
+        .min()
+        .unwrap_or(0)
+}
+
+/// A searched state with how it was reached.
+struct Node {
+    state: PuzzleState,
+    parent: Option<(usize, Action)>,
+    is_goal: bool,
+}
+
+/// Searching the shortest sequence of actions from a state to a goal
+/// with A* search.
+///
+/// Gives up after `state_limit` states were found.
+pub fn solve(initial: &PuzzleState, state_limit: usize) -> Solution {
+    let with_histories = initial
+        .layout
+        .gates
+        .iter()
+        .any(|g| g.logikind == LogiKind::Undo);
+    let clock_cycle = initial.layout.clock_cycle();
+    // Ctrl+Z doesn't matter for searching
+    let mut initial = initial.clone();
+    initial.clear_history();
+    let mut best_costs =
+        HashMap::from([(StateKey::new(&initial, with_histories, clock_cycle), (0, 0))]);
+    let mut queue = BinaryHeap::from([Reverse(((estimate_steps(&initial), 0), (0, 0), 0))]);
+    let mut nodes = vec![Node {
+        is_goal: initial.reached_goal(),
+        state: initial,
+        parent: None,
+    }];
+
+    while let Some(Reverse((_, cost, index))) = queue.pop() {
+        if nodes[index].is_goal {
+            return Solution::Found(trace_back(&nodes, index));
+        }
+        if best_costs.get(&StateKey::new(
+            &nodes[index].state,
+            with_histories,
+            clock_cycle,
+        )) < Some(&cost)
+        {
+            // A cheaper way to this state was already found
+            continue;
+        }
+        for action in candidates() {
+            let mut next = nodes[index].state.clone();
+            let is_goal = match next.step(action.clone()) {
+                Outcome::Moved => false,
+                Outcome::Goal => true,
+                Outcome::Blocked | Outcome::BouncedByEqualGate { .. } | Outcome::Caught { .. } => {
+                    continue;
+                }
+            };
+            let action_cost = cost_of(&action);
+            let next_cost = (cost.0 + action_cost.0, cost.1 + action_cost.1);
+            let key = StateKey::new(&next, with_histories, clock_cycle);
+            if best_costs.get(&key).is_some_and(|c| *c <= next_cost) {
+                continue;
+            }
+            if nodes.len() >= state_limit {
+                return Solution::GaveUp;
+            }
+            best_costs.insert(key, next_cost);
+            let priority = (next_cost.0 + estimate_steps(&next), next_cost.1);
+            nodes.push(Node {
+                state: next,
+                parent: Some((index, action)),
+                is_goal,
+            });
+            queue.push(Reverse((priority, next_cost, nodes.len() - 1)));
+        }
+    }
+    Solution::Unsolvable
+}
+
+/// Collecting actions from the initial state to a node.
+fn trace_back(nodes: &[Node], mut index: usize) -> Vec<Action> {
+    let mut actions = Vec::new();
+    while let Some((parent, action)) = &nodes[index].parent {
+        actions.push(action.clone());
+        index = *parent;
+    }
+    actions.reverse();
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve_source(source: &str) -> Solution {
+        let aquarium = ConstructAquarium::from_toml(source).unwrap();
+        solve(&PuzzleState::from(&aquarium), DEFAULT_STATE_LIMIT)
+    }
+
+    /// The ranks of the result are tuned with these steps,
+    /// see `S_RANK_MAX` in src/ui/game_clear.rs.
+    #[test]
+    fn optimal_steps_of_the_built_in_pack() {
+        let steps = (0..8)
+            .map(|i| {
+                let path = format!(
+                    "{}/assets/stages/stage_{}.toml",
+                    env!("CARGO_MANIFEST_DIR"),
+                    i
+                );
+                solve_source(&std::fs::read_to_string(path).unwrap()).steps()
+            })
+            .collect::<Option<Vec<u32>>>();
+        assert_eq!(steps, Some(vec![17, 16, 23, 18, 24, 18, 17, 34]));
+    }
+
+    #[test]
+    fn walled_off_goal_is_unsolvable() {
+        let source = "stage_name = \"test\"\ncontent = \"\"\"\n    W \n    WE\n\"\"\"\nplayer_origin = [2, 0]\nplayer_defaultbits = [false]\n";
+        assert_eq!(solve_source(source), Solution::Unsolvable);
+    }
+
+    #[test]
+    fn pushing_bits_reaches_a_goal_sooner() {
+        // Sliding left takes 6 steps, but the body lengthened
+        // by the push gates reaches the goal in 5 steps
+        let source = "stage_name = \"test\"\ncontent = \"\"\"\n       zzz  \n   E        \n\"\"\"\nplayer_origin = [10, 0]\nplayer_defaultbits = [false]\n";
+        assert_eq!(solve_source(source).steps(), Some(5));
+    }
+
+    #[test]
+    fn waiting_for_a_clock_gate() {
+        // The gate shows 0 only after 2 steps, so the boxfish swims away and back
+        let source = "stage_name = \"test\"\ncontent = \"\"\"\n      \nWG1GWW\n   E  \n\"\"\"\nplayer_origin = [3, 2]\nplayer_defaultbits = [false]\n[[clocks]]\nat = [1, 1]\nperiod = 2\n";
+        assert_eq!(solve_source(source).steps(), Some(4));
+    }
+}
@@ -0,0 +1,355 @@
+//! Recording what the player did in a stage, and playing it back.
+//!
+//! A replay is saved into `replays` in the user data directory
+//! on every stage cleared, and played by `--replay <file>`.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, path::PathBuf, time::SystemTime};
+
+use crate::{
+    arguments::path_argument,
+    prelude::*,
+    stage_manager::{LoadStage, NextStage, continue_on_startup},
+    user_data::user_data_dir,
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Operated>()
+            .init_resource::<ReplayRecorder>()
+            .init_resource::<ReplayPlayback>()
+            .add_systems(
+                Startup,
+                start_replay_from_argument.after(continue_on_startup),
+            )
+            .add_systems(
+                Update,
+                (
+                    tick_playback,
+                    play_reset,
+                    toggle_fast_playback.run_if(|p: Res<ReplayPlayback>| p.is_playing()),
+                )
+                    // A replay may undo or reset after running out of steps
+                    .run_if(in_state(MacroStates::GamePlay).or(in_state(MacroStates::OutOfMoves))),
+            )
+            // Operations are written in Update, so they're all read here
+            .add_systems(
+                PostUpdate,
+                (
+                    forget_recording.run_if(on_event::<NewGame>),
+                    record_operations,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// The directory in the user data directory where replays are saved.
+const REPLAY_DIRECTORY: &str = "replays";
+
+/// How many secs between operations while playing back.
+const PLAYBACK_INTERVAL: f32 = 0.25;
+
+/// How many times as fast as usual the game goes on fast playback.
+const FAST_PLAYBACK_SPEED: f32 = 4.;
+
+/// An operation of the player, which is recorded in a replay.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Operation {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// The expand button was pressed.
+    Expand,
+    /// The expand button was released.
+    Shrink,
+    Undo,
+    Redo,
+    /// The stage was reset by holding R.
+    Reset,
+    /// Another boxfish was chosen to control.
+    Switch,
+}
+
+impl Operation {
+    /// Getting the operation of moving, or None when it doesn't move.
+    pub fn from_travel(travel: &Travel) -> Option<Self> {
+        match (&travel.direction, travel.amount.signum()) {
+            (Direction::Y, 1) => Some(Operation::Up),
+            (Direction::Y, -1) => Some(Operation::Down),
+            (Direction::X, -1) => Some(Operation::Left),
+            (Direction::X, 1) => Some(Operation::Right),
+            _ => None,
+        }
+    }
+    /// Getting how the operation moves the boxfish, if it's moving.
+    pub fn travel(&self) -> Option<Travel> {
+        let travel = |direction, amount| Some(Travel { direction, amount });
+        match self {
+            Operation::Up => travel(Direction::Y, 1),
+            Operation::Down => travel(Direction::Y, -1),
+            Operation::Left => travel(Direction::X, -1),
+            Operation::Right => travel(Direction::X, 1),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Event)]
+/// Emitted when the player operated the boxfish.
+///
+/// Moving is emitted only when it produced [OnMoved].
+pub struct Operated(pub Operation);
+
+/// Operations in a stage with which stage it was.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    /// The title of the stage pack.
+    pub pack: String,
+    pub stage_index: usize,
+    pub stage_name: String,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Resource, Default)]
+/// Operations in the stage currently played.
+pub struct ReplayRecorder {
+    /// The pack and the index of the stage being recorded.
+    stage: Option<(usize, usize)>,
+    stage_name: String,
+    operations: Vec<Operation>,
+}
+
+#[derive(Resource)]
+/// Operations waiting to be played back.
+///
+/// While playing back, the movement systems take operations
+/// from here instead of the keyboard or a gamepad.
+pub struct ReplayPlayback {
+    operations: VecDeque<Operation>,
+    /// The operation which can be taken on this frame.
+    pending: Option<Operation>,
+    timer: Timer,
+    /// Whether the stage is being replayed, so it shouldn't be recorded.
+    replaying: bool,
+}
+
+impl Default for ReplayPlayback {
+    fn default() -> Self {
+        Self {
+            operations: VecDeque::new(),
+            pending: None,
+            timer: Timer::from_seconds(PLAYBACK_INTERVAL, TimerMode::Repeating),
+            replaying: false,
+        }
+    }
+}
+
+impl ReplayPlayback {
+    pub fn is_playing(&self) -> bool {
+        self.pending.is_some() || !self.operations.is_empty()
+    }
+    /// Is the current stage started by a replay?
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+    /// Taking the pending operation if it's the given one.
+    pub fn take(&mut self, operation: Operation) -> bool {
+        let taken = self.pending == Some(operation);
+        if taken {
+            self.pending = None;
+        }
+        taken
+    }
+    /// Taking the pending operation if it's moving.
+    pub fn take_travel(&mut self) -> Option<Travel> {
+        let travel = self.pending.as_ref().and_then(Operation::travel);
+        if travel.is_some() {
+            self.pending = None;
+        }
+        travel
+    }
+}
+
+/// Loading the replay given by `--replay <file>`, then start playing it back.
+pub fn start_replay_from_argument(
+    mut stage_manager: ResMut<StageManager>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut new_game: EventWriter<NewGame>,
+    mut state: ResMut<NextState<MacroStates>>,
+) {
+    let Some(path) = path_argument("--replay") else {
+        return;
+    };
+    let replay = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| toml::from_str::<Replay>(&source).map_err(|e| e.to_string()))
+    {
+        Ok(replay) => replay,
+        Err(e) => {
+            warn!("The replay {} couldn't be read: {}", path.display(), e);
+            return;
+        }
+    };
+    let Some(pack) = stage_manager
+        .packs
+        .iter()
+        .position(|pack| pack.title == replay.pack)
+    else {
+        warn!("The pack \"{}\" of the replay is not found", replay.pack);
+        return;
+    };
+    stage_manager.pack = pack;
+    // The stage may be modified after the replay was recorded
+    let stage_name = stage_manager
+        .stages()
+        .get(replay.stage_index)
+        .and_then(|stage| stage.read().ok())
+        .and_then(|source| ConstructAquarium::from_toml(&source).ok())
+        .map(|aquarium| aquarium.stage_name);
+    if stage_name.as_ref() != Some(&replay.stage_name) {
+        warn!(
+            "The replay was recorded on \"{}\", but the stage is {:?} now",
+            replay.stage_name, stage_name
+        );
+    }
+    new_game.write(NewGame::Stage(replay.stage_index));
+    playback.operations = replay.operations.into();
+    playback.replaying = true;
+    playback.timer.reset();
+    state.set(MacroStates::GamePlay);
+}
+
+/// Making the next operation takable at every interval.
+pub fn tick_playback(
+    time: Res<Time>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    if !playback.is_playing() {
+        // Going back to the usual speed after the last operation
+        if virtual_time.relative_speed() != 1. {
+            virtual_time.set_relative_speed(1.);
+        }
+        return;
+    }
+    if playback.timer.tick(time.delta()).just_finished() && playback.pending.is_none() {
+        playback.pending = playback.operations.pop_front();
+    }
+}
+
+/// Resetting the stage when the pending operation is [Operation::Reset].
+pub fn play_reset(
+    mut playback: ResMut<ReplayPlayback>,
+    stage_manager: Res<StageManager>,
+    mut load_stage: EventWriter<LoadStage>,
+) {
+    if playback.take(Operation::Reset) {
+        load_stage.write(LoadStage {
+            index: stage_manager.index,
+        });
+    }
+}
+
+/// Switching between the usual speed and the fast one when Tab pressed.
+pub fn toggle_fast_playback(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        let speed = if virtual_time.relative_speed() > 1. {
+            1.
+        } else {
+            FAST_PLAYBACK_SPEED
+        };
+        virtual_time.set_relative_speed(speed);
+    }
+}
+
+/// Getting where a replay of a stage will be saved.
+fn replay_path(pack: &str, stage_index: usize) -> Option<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let pack = pack
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    Some(user_data_dir()?.join(REPLAY_DIRECTORY).join(format!(
+        "{}_{}_{}.toml",
+        pack,
+        stage_index + 1,
+        secs
+    )))
+}
+
+fn save_replay(replay: &Replay) -> Result<PathBuf, String> {
+    let path = replay_path(&replay.pack, replay.stage_index)
+        .ok_or("the user data directory is unknown")?;
+    let source = toml::to_string(replay).map_err(|e| e.to_string())?;
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, source).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Recording operations of the player, then saving them on the stage cleared.
+///
+/// The recording starts over when another stage was loaded.
+pub fn record_operations(
+    mut recorder: ResMut<ReplayRecorder>,
+    mut playback: ResMut<ReplayPlayback>,
+    stage_manager: Res<StageManager>,
+    puzzle: Res<CurrentPuzzle>,
+    mut construct_aquarium: EventReader<ConstructAquarium>,
+    mut operated: EventReader<Operated>,
+    mut next_stage: EventReader<NextStage>,
+) {
+    recorder
+        .operations
+        .extend(operated.read().map(|Operated(operation)| *operation));
+
+    // Saving before the next stage is recorded
+    if next_stage.read().count() > 0 && puzzle.reached_goal() {
+        let operations = std::mem::take(&mut recorder.operations);
+        // A replay of a replay is the same as the original one
+        let replaying = std::mem::take(&mut playback.replaying);
+        if let (Some((pack, stage_index)), false) = (recorder.stage.take(), replaying) {
+            let replay = Replay {
+                pack: stage_manager
+                    .packs
+                    .get(pack)
+                    .map(|p| p.title.clone())
+                    .unwrap_or_default(),
+                stage_index,
+                stage_name: recorder.stage_name.clone(),
+                operations,
+            };
+            match save_replay(&replay) {
+                Ok(path) => info!("The replay was saved to {}", path.display()),
+                Err(e) => warn!("The replay couldn't be saved: {}", e),
+            }
+        }
+    }
+
+    for aquarium in construct_aquarium.read() {
+        let stage = Some((stage_manager.pack, stage_manager.index));
+        // Resetting the stage keeps recording
+        if recorder.stage != stage {
+            recorder.stage = stage;
+            recorder.operations.clear();
+        }
+        recorder.stage_name = aquarium.stage_name.clone();
+    }
+}
+
+/// Starting the recording over on a new game, even if it starts from the same stage.
+pub fn forget_recording(mut recorder: ResMut<ReplayRecorder>) {
+    recorder.stage = None;
+}
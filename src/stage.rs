@@ -1,4 +1,6 @@
-mod construction;
+pub mod construction;
+mod enemy;
+pub mod lint;
 mod resource;
 mod visual;
 
@@ -11,11 +13,26 @@ pub struct AquariumPlugin;
 
 impl Plugin for AquariumPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<ConstructionCompleted>()
-            .init_resource::<AquariumResource>()
+        app.init_resource::<AquariumResource>()
             .add_systems(Startup, resource::init_aquarium_resource)
             .add_systems(Update, visual::highlight_incorrect_bits)
             .add_systems(Update, visual::goal_swaying)
+            .add_systems(Update, visual::open_and_close_doors)
+            .add_systems(
+                Update,
+                visual::hide_picked_pearls.run_if(resource_changed::<CurrentPuzzle>),
+            )
+            .add_systems(
+                Update,
+                (visual::flip_clock_bits, visual::clock_tick_animation),
+            )
+            .add_systems(
+                Update,
+                (
+                    enemy::follow_puzzle.run_if(resource_changed::<CurrentPuzzle>),
+                    enemy::swim_to_ideal_position,
+                ),
+            )
             .add_systems(Update, parse_stage);
     }
 }
@@ -41,18 +58,19 @@ pub struct SemiCollidable;
 #[derive(Component, Debug)]
 /// This is a component to highlight gates with red colour,
 /// which was different from a collided player's register.
+/// Goals reached with a wrong register are highlighted too.
 pub struct IncorrectBit {
     pub remaining: u8,
 }
 
 #[derive(Component)]
 /// This is a component for logical gates' register.
-pub struct LogiRegister {
-    pub boolean: bool,
-    pub logikind: LogiKind,
-}
+///
+/// The boolean and the kind of the gate are held by
+/// [AquariumLayout](crate::puzzle::AquariumLayout).
+pub struct LogiRegister;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum LogiKind {
     And,
     Or,
@@ -60,6 +78,77 @@ pub enum LogiKind {
     Xor,
     Undo,
     Equal,
+    Nand,
+    Nor,
+    Xnor,
+    /// Adding the gate's bits to the register as an unsigned integer.
+    Add,
+}
+
+/// An operation on the whole register, bits are ordered by
+/// [BitIter](crate::boxfish::BitIter) from the lowest.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RegisterOp {
+    /// Moving every bit to the higher, then the lowest gets 0.
+    ShiftLeft,
+    /// Moving every bit to the lower, then the highest gets 0.
+    ShiftRight,
+    /// Moving every bit to the higher, then the highest comes to the lowest.
+    RotateLeft,
+    /// Moving every bit to the lower, then the lowest comes to the highest.
+    RotateRight,
+    /// Adding a new highest bit with the value, which makes the body longer.
+    Push(bool),
+    /// Removing the highest bit, which makes the body shorter.
+    /// The last bit is never removed.
+    Pop,
+}
+
+/// A tile interpreted from a charactor of a stage,
+/// before any entity is spawned for it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tile {
+    Wall,
+    Goal,
+    /// The tail or the head of a logical gate.
+    /// The gate lines up along the direction, from the tail to the head.
+    /// A horizontal one has its tail on the left, and a vertical one on the top.
+    GateEnd {
+        logikind: LogiKind,
+        is_head: bool,
+        direction: Direction,
+    },
+    /// A boolean stored in a logical gate.
+    GateBit {
+        logikind: LogiKind,
+        boolean: bool,
+    },
+    /// A warp tile, which carries the boxfish to another one of the same pair.
+    Warp {
+        pair: u32,
+    },
+    /// A water current, which carries the boxfish toward the flow
+    /// when the head ends a move on it.
+    Current {
+        flow: IVec2,
+    },
+    /// A gate which works on the whole register when the head moves onto it.
+    RegisterGate {
+        op: RegisterOp,
+    },
+    /// A pressure plate, which opens doors of the same group while pressed.
+    Plate {
+        group: char,
+    },
+    /// A door, which is a wall until a plate of the same group is pressed.
+    /// The group is the uppercase letter of the door.
+    Door {
+        group: char,
+    },
+    /// A door, which is a wall until an adder gate overflows the register.
+    OverflowDoor,
+    /// A pearl, which is picked up when the boxfish passes over it.
+    Pearl,
 }
 
 const TILE_LAYER: f32 = 0.;
@@ -68,23 +157,34 @@ const TILE_LAYER: f32 = 0.;
 pub struct Goal;
 
 #[derive(Component)]
-pub struct StageCompleted;
+/// This is a component for doors, which are shown only while closed.
+pub struct Door;
+
+#[derive(Component)]
+/// This is a component for pearls, which are shown until picked up.
+pub struct Pearl;
+
+#[derive(Component)]
+/// This is a component for a bit of a clock gate which just flipped.
+pub struct ClockTick {
+    progress: f32,
+}
 
-#[derive(Event)]
-pub struct ConstructionCompleted;
+#[derive(Component)]
+pub struct StageCompleted;
 
 pub fn parse_stage(
     mut commands: Commands,
     tile_resource: Res<AquariumResource>,
     old_tiles: Query<Entity, With<Tiles>>,
     mut on_loaded: EventReader<ConstructAquarium>,
-    mut construction_completed: EventWriter<ConstructionCompleted>,
 ) {
     if let Some(aq) = on_loaded.read().next() {
         for t in old_tiles {
             commands.entity(t).despawn();
         }
+        construction::spawn_goal_patterns(&aq.goal_patterns, &mut commands, &tile_resource);
+        enemy::spawn_enemies(&aq.enemies, &mut commands, &tile_resource);
         construction::chars_into_tiles(&aq.content, commands, tile_resource);
-        construction_completed.write(ConstructionCompleted);
     }
 }
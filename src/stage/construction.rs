@@ -1,11 +1,115 @@
 mod each_char;
+mod each_column;
 mod each_line;
 
 use super::{
-    Goal, IncorrectBit, LogiKind, LogiRegister, TILE_LAYER, Tiles, resource::AquariumResource,
+    Door, Goal, IncorrectBit, LogiKind, LogiRegister, Pearl, RegisterOp, SemiCollidable,
+    TILE_LAYER, Tile, Tiles, resource::AquariumResource,
 };
-use crate::prelude::*;
+use crate::{prelude::*, stage_manager::GoalPattern};
 use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+/// A reason why a charactor of a stage couldn't be interprinted properly.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TileError {
+    /// The charactor means nothing, so it's ignored.
+    UnknownCharactor(char),
+    /// A boolean came before any logical gate's tail.
+    BitWithoutTail,
+    /// Another kind of logical gate came before the head of a gate.
+    MismatchedHead { tail: LogiKind, head: LogiKind },
+    /// A logical gate's tail was never closed by its head.
+    MissingHead(LogiKind),
+    /// A warp tile doesn't have exactly one partner of the same number.
+    UnpairedWarp(u32),
+}
+
+impl std::fmt::Display for TileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TileError::UnknownCharactor(c) => write!(f, "unknown charactor '{}'", c),
+            TileError::BitWithoutTail => {
+                write!(f, "expected a logigate's tail before any boolean")
+            }
+            TileError::MismatchedHead { tail, head } => write!(
+                f,
+                "the head of a {:?} gate was expected, but found a {:?} gate",
+                tail, head
+            ),
+            TileError::MissingHead(logikind) => {
+                write!(f, "the {:?} gate has no head in the line", logikind)
+            }
+            TileError::UnpairedWarp(pair) => {
+                write!(f, "the warp '{}' should appear exactly twice", pair)
+            }
+        }
+    }
+}
+
+/// A [TileError] with where it's found.
+///
+/// Both the line and the column count from 0,
+/// and the line counts from the top of the stage.
+#[derive(Clone, Debug)]
+pub struct TileProblem {
+    pub line: usize,
+    pub column: usize,
+    pub error: TileError,
+}
+
+/// Interprint a whole stage as tiles without spawning anything.
+///
+/// The lowest line of the stage is y = 0. Broken charactors
+/// are skipped, and returned as problems.
+pub fn interprint_aquarium(aquarium: &str) -> (Vec<(IVec2, Tile)>, Vec<TileProblem>) {
+    let height = aquarium.lines().count();
+    let to_pos = |line: usize, x: usize| IVec2::new(x as i32, (height - line - 1) as i32);
+    // Vertical gates are found first, since lines can't tell them
+    let grid = aquarium
+        .lines()
+        .map(|l| l.chars().collect())
+        .collect::<Vec<Vec<char>>>();
+    let vertical = each_column::interprint_vertical_gates(&grid);
+    let mut tiles = vertical
+        .iter()
+        .map(|(line, x, tile)| (to_pos(*line, *x), *tile))
+        .collect::<Vec<(IVec2, Tile)>>();
+    let mut problems = Vec::new();
+    for (line, s) in aquarium.lines().enumerate() {
+        let skipped = vertical
+            .iter()
+            .filter(|(l, ..)| *l == line)
+            .map(|(_, x, _)| *x)
+            .collect::<Vec<usize>>();
+        tiles.extend(
+            each_line::interprint_each_line_as_tile(s, line, &skipped, &mut problems)
+                .into_iter()
+                .map(|(x, tile)| (to_pos(line, x), tile)),
+        );
+    }
+    // Each warp needs its partner
+    for (pos, tile) in &tiles {
+        if let Tile::Warp { pair } = tile
+            && tiles.iter().filter(|(_, t)| t == tile).count() != 2
+        {
+            problems.push(TileProblem {
+                line: height - pos.y as usize - 1,
+                column: pos.x as usize,
+                error: TileError::UnpairedWarp(*pair),
+            });
+        }
+    }
+    (tiles, problems)
+}
+
+/// Getting the width and the height of a stage in tiles.
+pub fn aquarium_size(aquarium: &str) -> UVec2 {
+    UVec2::new(
+        aquarium.lines().map(|l| l.len()).max().unwrap_or(0) as u32,
+        aquarium.lines().collect::<Vec<&str>>().len() as u32,
+    )
+}
 
 /// Constructing a stage with interprinting given string.
 pub fn chars_into_tiles(
@@ -13,17 +117,208 @@ pub fn chars_into_tiles(
     mut commands: Commands,
     tile_resource: Res<AquariumResource>,
 ) {
-    let aquarium_size = UVec2::new(
-        aquarium.lines().map(|l| l.len()).max().unwrap_or(0) as u32,
-        aquarium.lines().collect::<Vec<&str>>().len() as u32,
-    );
-
     // Construct stages' inside
-    for (y, s) in aquarium.lines().rev().enumerate() {
-        each_line::interprint_each_line_as_tile(&mut commands, s, y, &tile_resource);
+    for (pos, tile) in interprint_aquarium(aquarium).0 {
+        spawn_tile(&mut commands, pos, tile, &tile_resource);
     }
     // Construct stages' outline
-    construct_stage_outline(&mut commands, &tile_resource, aquarium_size);
+    construct_stage_outline(&mut commands, &tile_resource, aquarium_size(aquarium));
+}
+
+/// Showing the patterns of goals above them, in half size.
+///
+/// The lowest bit is on the right, as the boxfish faces right.
+pub fn spawn_goal_patterns(
+    patterns: &[GoalPattern],
+    commands: &mut Commands,
+    tile_resource: &Res<AquariumResource>,
+) {
+    let bit_size = TILE_SIZE as f32 / 2.;
+    for pattern in patterns {
+        let above = TileCoords::ivec2_to_vec2(pattern.at) + Vec2::Y * TILE_SIZE as f32;
+        let width = bit_size * pattern.bits.len() as f32;
+        for (i, bit) in pattern.bits.iter().enumerate() {
+            let x = width / 2. - bit_size * (i as f32 + 0.5);
+            let index = if *bit { (0, 0) } else { (1, 0) };
+            commands.spawn((
+                generate_tile_from_index(index.0, index.1, tile_resource),
+                Transform::from_translation((above + Vec2::X * x).extend(TILE_LAYER))
+                    .with_scale(Vec3::splat(0.5)),
+                Tiles,
+            ));
+        }
+    }
+}
+
+fn generate_tile_from_index(x: usize, y: usize, tile_resource: &Res<AquariumResource>) -> Sprite {
+    // Generating a Sprite component from the tilemap index
+    Sprite::from_atlas_image(
+        tile_resource.tile_sprite.clone(),
+        TextureAtlas {
+            layout: tile_resource.tile_layout.clone(),
+            index: x + y * 16,
+        },
+    )
+}
+
+/// Getting the tilemap index of a logical gate's tail.
+/// The head is always placed on the right of the tail.
+fn logikind_to_tilemap_index(logikind: LogiKind) -> (usize, usize) {
+    match logikind {
+        LogiKind::And => (0, 1),
+        LogiKind::Or => (0, 2),
+        LogiKind::Not => (0, 3),
+        LogiKind::Xor => (0, 4),
+        LogiKind::Equal => (2, 0),
+        LogiKind::Undo => (0, 5),
+        LogiKind::Nand => (0, 6),
+        LogiKind::Nor => (0, 7),
+        LogiKind::Xnor => (0, 8),
+        LogiKind::Add => (0, 9),
+    }
+}
+
+/// Spawn an entity for an interprinted tile.
+fn spawn_tile(
+    commands: &mut Commands,
+    pos: IVec2,
+    tile: Tile,
+    tile_resource: &Res<AquariumResource>,
+) {
+    // Combining TileCoords and Transform for simplicity
+    let coords = (
+        TileCoords { tile_pos: pos },
+        Transform::from_translation(TileCoords::ivec2_to_vec2(pos).extend(TILE_LAYER)),
+    );
+    match tile {
+        Tile::GateEnd {
+            logikind,
+            is_head,
+            direction,
+        } => {
+            let index = logikind_to_tilemap_index(logikind);
+            let (tile_coords, mut transform) = coords;
+            // Turning the tail upward and the head downward
+            if direction == Direction::Y {
+                transform.rotate_z(-FRAC_PI_2);
+            }
+            commands.spawn((
+                generate_tile_from_index(index.0 + is_head as usize, index.1, tile_resource),
+                SemiCollidable,
+                tile_coords,
+                transform,
+                Tiles,
+            ));
+        }
+        Tile::GateBit { boolean, .. } => {
+            let index = if boolean { (0, 0) } else { (1, 0) };
+            commands.spawn((
+                generate_tile_from_index(index.0, index.1, tile_resource),
+                LogiRegister,
+                Tiles,
+                IncorrectBit { remaining: 0 },
+                SemiCollidable,
+                coords,
+            ));
+        }
+        Tile::Wall => {
+            commands.spawn((
+                Sprite::from_image(tile_resource.wall_sprite.clone()),
+                Tiles,
+                Collidable,
+                coords,
+            ));
+        }
+        Tile::Goal => {
+            commands.spawn((
+                Sprite::from_image(tile_resource.goal_sprite.clone()),
+                Tiles,
+                Goal,
+                IncorrectBit { remaining: 0 },
+                coords,
+            ));
+        }
+        Tile::Warp { pair } => {
+            let mut sprite = generate_tile_from_index(2, 1, tile_resource);
+            // Pairs are told apart by their colours
+            sprite.color = Color::hsl(pair as f32 * 45., 0.8, 0.7);
+            commands.spawn((sprite, Tiles, coords));
+        }
+        Tile::Plate { group } => {
+            let mut sprite = generate_tile_from_index(4, 1, tile_resource);
+            sprite.color = group_colour(group);
+            commands.spawn((sprite, Tiles, coords));
+        }
+        Tile::Door { group } => {
+            let mut sprite = generate_tile_from_index(3, 1, tile_resource);
+            sprite.color = group_colour(group);
+            commands.spawn((sprite, Door, Tiles, coords));
+        }
+        Tile::OverflowDoor => {
+            commands.spawn((
+                generate_tile_from_index(3, 2, tile_resource),
+                Door,
+                Tiles,
+                coords,
+            ));
+        }
+        Tile::Pearl => {
+            let mut sprite = generate_tile_from_index(11, 1, tile_resource);
+            sprite.color = Color::srgb(1., 0.9, 0.95);
+            commands.spawn((sprite, Pearl, Tiles, coords));
+        }
+        Tile::Current { flow } => {
+            let (tile_coords, mut transform) = coords;
+            transform.rotate_z(Vec2::X.angle_to(flow.as_vec2()));
+            let mut sprite = generate_tile_from_index(5, 1, tile_resource);
+            sprite.color = Color::srgb(0.6, 0.8, 1.);
+            commands.spawn((sprite, Tiles, tile_coords, transform));
+        }
+        Tile::RegisterGate { op } => {
+            // The lowest bit is the nearest to the head, so left is farther from it
+            let (x, flip_x) = match op {
+                RegisterOp::ShiftLeft => (6, false),
+                RegisterOp::ShiftRight => (6, true),
+                RegisterOp::RotateLeft => (7, false),
+                RegisterOp::RotateRight => (7, true),
+                RegisterOp::Push(boolean) => (8 + boolean as usize, false),
+                RegisterOp::Pop => (10, false),
+            };
+            let mut sprite = generate_tile_from_index(x, 1, tile_resource);
+            sprite.flip_x = flip_x;
+            commands.spawn((sprite, Tiles, coords));
+        }
+    }
+}
+
+/// Getting the colour of plates and doors which are linked together.
+fn group_colour(group: char) -> Color {
+    Color::hsl((group as u32 - 'P' as u32) as f32 * 120., 0.8, 0.7)
+}
+
+/// Getting tiles of stages' outline with their tilemap indices.
+pub fn outline_tiles(aquarium_size: UVec2) -> Vec<((usize, usize), IVec2)> {
+    let isize = IVec2::new(aquarium_size.x as i32, aquarium_size.y as i32);
+    let mut tiles = Vec::new();
+    // A upper left
+    tiles.push(((0, 0), IVec2::new(-1, isize.y)));
+    // Upper and downer sides
+    for x in 0..isize.x {
+        tiles.push(((1, 0), IVec2::new(x, isize.y)));
+        tiles.push(((1, 2), IVec2::new(x, -1)));
+    }
+    // A upper right
+    tiles.push(((2, 0), IVec2::new(isize.x, isize.y)));
+    // Right and left sides
+    for y in 0..isize.y {
+        tiles.push(((0, 1), IVec2::new(-1, y)));
+        tiles.push(((2, 1), IVec2::new(isize.x, y)));
+    }
+    // A downer left
+    tiles.push(((0, 2), IVec2::new(-1, -1)));
+    // A downer right
+    tiles.push(((2, 2), IVec2::new(isize.x, -1)));
+    tiles
 }
 
 /// Construct stages' outline.
@@ -32,8 +327,8 @@ pub fn construct_stage_outline(
     tile_resource: &Res<AquariumResource>,
     aquarium_size: UVec2,
 ) {
-    let generate_a_outlines_tile = |index: (usize, usize), pos: IVec2| {
-        (
+    for (index, pos) in outline_tiles(aquarium_size) {
+        commands.spawn((
             Collidable,
             TileCoords::from_ivec2(pos),
             Transform::from_xyz(
@@ -49,28 +344,6 @@ pub fn construct_stage_outline(
                 },
             ),
             Tiles,
-        )
-    };
-    let isize = IVec2::new(aquarium_size.x as i32, aquarium_size.y as i32);
-    // A upper left
-    commands.spawn(generate_a_outlines_tile((0, 0), IVec2::new(-1, isize.y)));
-    // Upper and downer sides
-    for x in 0..isize.x {
-        commands.spawn(generate_a_outlines_tile((1, 0), IVec2::new(x, isize.y)));
-        commands.spawn(generate_a_outlines_tile((1, 2), IVec2::new(x, -1)));
+        ));
     }
-    // A upper right
-    commands.spawn(generate_a_outlines_tile(
-        (2, 0),
-        IVec2::new(isize.x, isize.y),
-    ));
-    // Right and left sides
-    for y in 0..isize.y {
-        commands.spawn(generate_a_outlines_tile((0, 1), IVec2::new(-1, y)));
-        commands.spawn(generate_a_outlines_tile((2, 1), IVec2::new(isize.x, y)));
-    }
-    // A downer left
-    commands.spawn(generate_a_outlines_tile((0, 2), IVec2::new(-1, -1)));
-    // A downer right
-    commands.spawn(generate_a_outlines_tile((2, 2), IVec2::new(isize.x, -1)));
 }
@@ -1,128 +1,128 @@
-use super::{
-    super::resource::AquariumResource, Goal, IncorrectBit, LogiKind, LogiRegister, TILE_LAYER,
-    Tiles, each_line::LineContextContainer,
-};
-use crate::{prelude::*, stage::SemiCollidable};
-use bevy::prelude::*;
+use super::{LogiKind, RegisterOp, Tile, TileError, each_line::LineContextContainer};
+use crate::prelude::*;
+use bevy::math::IVec2;
 
-/// This is a support function to generating
-/// logical gate with minimal boiler plate.
-fn generate_logical_gate(
-    coords: (TileCoords, Transform),
-    tilemap_index: (usize, usize),
-    logikind: LogiKind,
-    state: &mut LineContextContainer,
-    tile_resource: &Res<AquariumResource>,
-) -> (Sprite, SemiCollidable, TileCoords, Transform, Tiles) {
-    let gate_common_components = (coords.clone(), Tiles);
-    let sprite: Sprite;
-    let do_spawn_head = if let Some(bkind) = state.bitkind {
+/// This is a support function to deciding
+/// whether a logical gate's charactor is a tail or a head.
+fn interprint_logical_gate(logikind: LogiKind, x: usize, state: &mut LineContextContainer) -> Tile {
+    let is_head = if let Some(bkind) = state.bitkind {
         bkind == logikind && state.tail_found
     } else {
         false
     };
-    if do_spawn_head {
-        sprite = generate_tile_from_index(tilemap_index.0 + 1, tilemap_index.1, tile_resource);
-    } else {
-        sprite = generate_tile_from_index(tilemap_index.0, tilemap_index.1, tile_resource);
+    if !is_head {
+        // Another kind of gate came before the head was found
+        if let (true, Some(tail)) = (state.tail_found, state.bitkind) {
+            state.problems.push((
+                x,
+                TileError::MismatchedHead {
+                    tail,
+                    head: logikind,
+                },
+            ));
+        }
         state.bitkind = Some(logikind);
+        state.tail_x = x;
+    }
+    state.tail_found = !is_head;
+    Tile::GateEnd {
+        logikind,
+        is_head,
+        direction: Direction::X,
     }
-    state.tail_found = !do_spawn_head;
-    let ((tile_coords, transform), tile) = gate_common_components;
-    (sprite, SemiCollidable, tile_coords, transform, tile)
 }
 
-fn generate_tile_from_index(x: usize, y: usize, tile_resource: &Res<AquariumResource>) -> Sprite {
-    // Generating a Sprite component from the tilemap index
-    Sprite::from_atlas_image(
-        tile_resource.tile_sprite.clone(),
-        TextureAtlas {
-            layout: tile_resource.tile_layout.clone(),
-            index: x + y * 16,
-        },
-    )
+/// Getting the kind of a logical gate written as a charactor.
+pub fn charactor_to_logikind(charactor: char) -> Option<LogiKind> {
+    match charactor {
+        'A' => Some(LogiKind::And),
+        'O' => Some(LogiKind::Or),
+        'N' => Some(LogiKind::Not),
+        'X' => Some(LogiKind::Xor),
+        'G' => Some(LogiKind::Equal),
+        'U' => Some(LogiKind::Undo),
+        // Negated gates are written in the lowercase of the original ones
+        'a' => Some(LogiKind::Nand),
+        'o' => Some(LogiKind::Nor),
+        'x' => Some(LogiKind::Xnor),
+        '+' => Some(LogiKind::Add),
+        _ => None,
+    }
 }
 
-/// Generate an tile from a given charactor and contexts.
+/// Getting a boolean written as a charactor.
+pub fn charactor_to_boolean(charactor: char) -> Option<bool> {
+    match charactor {
+        '0' => Some(false),
+        '1' => Some(true),
+        _ => None,
+    }
+}
+
+/// Interprint a given charactor as a tile with contexts.
+///
+/// Returns None when the charactor means nothing (e.g. a space),
+/// or when it's broken. Problems are recorded into the contexts.
 pub fn interprint_each_char_as_tile(
-    commands: &mut Commands,
     charactor: char,
     x: usize,
-    y: usize,
-    tile_resource: &Res<AquariumResource>,
     state: &mut LineContextContainer,
-) {
-    // Combining TileCoords and Transform for simplicity
-    let coords = (
-        TileCoords {
-            tile_pos: IVec2::new(x as i32, y as i32),
-        },
-        Transform::from_xyz((x * TILE_SIZE) as f32, (y * TILE_SIZE) as f32, TILE_LAYER),
-    );
-    // Common components for a Bit
-    let bit_common_components = (
-        Tiles,
-        IncorrectBit { remaining: 0 },
-        SemiCollidable,
-        coords.clone(),
-    );
-
-    const LOGIKIND_UNDEFINED_MESSAGE: &str =
-        "Parse Error: Expected a logigate's tail before any boolean";
-    let logigate = match charactor {
-        'A' => Some(((0, 1), LogiKind::And)),
-        'O' => Some(((0, 2), LogiKind::Or)),
-        'N' => Some(((0, 3), LogiKind::Not)),
-        'X' => Some(((0, 4), LogiKind::Xor)),
-        'G' => Some(((2, 0), LogiKind::Equal)),
-        'U' => Some(((0, 5), LogiKind::Undo)),
-        _ => None,
-    };
-    if let Some((index, logikind)) = logigate {
-        commands.spawn(generate_logical_gate(
-            coords,
-            index,
-            logikind,
-            state,
-            tile_resource,
-        ));
-        return;
+) -> Option<Tile> {
+    if let Some(logikind) = charactor_to_logikind(charactor) {
+        return Some(interprint_logical_gate(logikind, x, state));
     }
 
-    let boolean = match charactor {
-        '0' => Some(((1, 0), false)),
-        '1' => Some(((0, 0), true)),
-        _ => None,
-    };
-    if let Some((index, boolean)) = boolean {
-        commands.spawn((
-            generate_tile_from_index(index.0, index.1, tile_resource),
-            LogiRegister {
-                boolean: boolean,
-                logikind: state.bitkind.expect(LOGIKIND_UNDEFINED_MESSAGE),
-            },
-            bit_common_components,
-        ));
-        return;
+    if let Some(boolean) = charactor_to_boolean(charactor) {
+        return match state.bitkind {
+            Some(logikind) => Some(Tile::GateBit { logikind, boolean }),
+            None => {
+                state.problems.push((x, TileError::BitWithoutTail));
+                None
+            }
+        };
     }
 
     match charactor {
-        'W' => {
-            commands.spawn((
-                Sprite::from_image(tile_resource.wall_sprite.clone()),
-                Tiles,
-                Collidable,
-                coords,
-            ));
-        }
-        'E' => {
-            commands.spawn((
-                Sprite::from_image(tile_resource.goal_sprite.clone()),
-                Tiles,
-                Goal,
-                coords,
-            ));
+        '2'..='9' => charactor.to_digit(10).map(|pair| Tile::Warp { pair }),
+        '>' => Some(Tile::Current { flow: IVec2::X }),
+        '<' => Some(Tile::Current { flow: IVec2::NEG_X }),
+        '^' => Some(Tile::Current { flow: IVec2::Y }),
+        'v' => Some(Tile::Current { flow: IVec2::NEG_Y }),
+        '[' => Some(Tile::RegisterGate {
+            op: RegisterOp::ShiftLeft,
+        }),
+        ']' => Some(Tile::RegisterGate {
+            op: RegisterOp::ShiftRight,
+        }),
+        '(' => Some(Tile::RegisterGate {
+            op: RegisterOp::RotateLeft,
+        }),
+        ')' => Some(Tile::RegisterGate {
+            op: RegisterOp::RotateRight,
+        }),
+        'z' => Some(Tile::RegisterGate {
+            op: RegisterOp::Push(false),
+        }),
+        'i' => Some(Tile::RegisterGate {
+            op: RegisterOp::Push(true),
+        }),
+        '-' => Some(Tile::RegisterGate {
+            op: RegisterOp::Pop,
+        }),
+        'P' | 'Q' | 'R' => Some(Tile::Door { group: charactor }),
+        'p' | 'q' | 'r' => Some(Tile::Plate {
+            group: charactor.to_ascii_uppercase(),
+        }),
+        'C' => Some(Tile::OverflowDoor),
+        '@' => Some(Tile::Pearl),
+        'W' => Some(Tile::Wall),
+        'E' => Some(Tile::Goal),
+        ' ' => None,
+        _ => {
+            state
+                .problems
+                .push((x, TileError::UnknownCharactor(charactor)));
+            None
         }
-        _ => (),
     }
 }
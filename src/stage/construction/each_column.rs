@@ -0,0 +1,47 @@
+use super::{
+    Tile,
+    each_char::{charactor_to_boolean, charactor_to_logikind},
+};
+use crate::prelude::*;
+
+/// Finding logical gates written vertically, then returns tiles
+/// with their line indices and x coords.
+///
+/// A vertical gate is a tail, bits stacked downward, and the head
+/// of the same kind just below them, all in a column.
+pub fn interprint_vertical_gates(lines: &[Vec<char>]) -> Vec<(usize, usize, Tile)> {
+    let char_at = |line: usize, x: usize| lines.get(line).and_then(|l| l.get(x)).copied();
+    let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+    let mut tiles = Vec::new();
+    for x in 0..width {
+        let mut line = 0;
+        while line < lines.len() {
+            let Some(logikind) = char_at(line, x).and_then(charactor_to_logikind) else {
+                line += 1;
+                continue;
+            };
+            let bits = (line + 1..)
+                .map_while(|l| char_at(l, x).and_then(charactor_to_boolean))
+                .collect::<Vec<bool>>();
+            let head_line = line + bits.len() + 1;
+            if bits.is_empty()
+                || char_at(head_line, x).and_then(charactor_to_logikind) != Some(logikind)
+            {
+                line += 1;
+                continue;
+            }
+            let end = |is_head| Tile::GateEnd {
+                logikind,
+                is_head,
+                direction: Direction::Y,
+            };
+            tiles.push((line, x, end(false)));
+            for (i, boolean) in bits.into_iter().enumerate() {
+                tiles.push((line + i + 1, x, Tile::GateBit { logikind, boolean }));
+            }
+            tiles.push((head_line, x, end(true)));
+            line = head_line + 1;
+        }
+    }
+    tiles
+}
@@ -1,5 +1,4 @@
-use super::{super::resource::AquariumResource, LogiKind, each_char::interprint_each_char_as_tile};
-use bevy::prelude::*;
+use super::{LogiKind, Tile, TileError, TileProblem, each_char::interprint_each_char_as_tile};
 
 /// LogiKindを類，真理値を真としたとき，
 ///
@@ -10,20 +9,50 @@ use bevy::prelude::*;
 pub struct LineContextContainer {
     pub bitkind: Option<LogiKind>,
     pub tail_found: bool,
+    /// The x coords of the last tail.
+    pub tail_x: usize,
+    /// Problems found in the line, with their x coords.
+    pub problems: Vec<(usize, TileError)>,
 }
 
+/// Interprint a line of a stage, then returns tiles with their x coords.
+///
+/// Charactors at `skipped` are parts of vertical gates, so they're
+/// treated as spaces. Problems found in the line are pushed into `problems`.
 pub fn interprint_each_line_as_tile(
-    commands: &mut Commands,
     line: &str,
-    y: usize,
-    tile_resource: &Res<AquariumResource>,
-) {
+    line_index: usize,
+    skipped: &[usize],
+    problems: &mut Vec<TileProblem>,
+) -> Vec<(usize, Tile)> {
     let mut state = LineContextContainer {
         bitkind: None,
         tail_found: false,
+        tail_x: 0,
+        problems: Vec::new(),
     };
     // ここからタイルそれぞれについての処理
-    for (x, c) in line.chars().enumerate() {
-        interprint_each_char_as_tile(commands, c, x, y, tile_resource, &mut state);
+    let tiles = line
+        .chars()
+        .enumerate()
+        .filter(|(x, _)| !skipped.contains(x))
+        .filter_map(|(x, c)| interprint_each_char_as_tile(c, x, &mut state).map(|tile| (x, tile)))
+        .collect();
+    // 行末まで頭が見つからなかった
+    if let (true, Some(logikind)) = (state.tail_found, state.bitkind) {
+        state
+            .problems
+            .push((state.tail_x, TileError::MissingHead(logikind)));
     }
+    problems.extend(
+        state
+            .problems
+            .into_iter()
+            .map(|(column, error)| TileProblem {
+                line: line_index,
+                column,
+                error,
+            }),
+    );
+    tiles
 }
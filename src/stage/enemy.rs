@@ -0,0 +1,77 @@
+//! Showing enemy fish, which swim as [PuzzleState] says.
+
+use super::{AquariumResource, Tiles};
+use crate::{prelude::*, stage_manager::EnemyPatrol};
+use bevy::prelude::*;
+
+const ENEMY_LAYER: f32 = 5.;
+
+/// How many seconds will an enemy take to swim a tile.
+const SECONDS_PER_TILE: f32 = 0.2;
+
+#[derive(Component)]
+/// This is a component for enemy fish.
+pub struct EnemyFish {
+    /// The index in [PuzzleState::enemies].
+    index: usize,
+}
+
+/// Getting how an enemy is turned, since the sprite faces up.
+fn heading_to_rotation(heading: IVec2) -> Quat {
+    if heading == IVec2::ZERO {
+        return Quat::IDENTITY;
+    }
+    Quat::from_rotation_z(Vec2::Y.angle_to(heading.as_vec2()))
+}
+
+pub fn spawn_enemies(
+    patrols: &[EnemyPatrol],
+    commands: &mut Commands,
+    tile_resource: &Res<AquariumResource>,
+) {
+    for (index, patrol) in patrols.iter().enumerate() {
+        let mut sprite = Sprite::from_image(tile_resource.fish_sprite.clone());
+        sprite.color = Color::srgb(1., 0.5, 0.4);
+        commands.spawn((
+            sprite,
+            Transform::from_translation(
+                TileCoords::ivec2_to_vec2(patrol.origin).extend(ENEMY_LAYER),
+            )
+            .with_rotation(heading_to_rotation(patrol.heading)),
+            TileCoords {
+                tile_pos: patrol.origin,
+            },
+            EnemyFish { index },
+            Tiles,
+        ));
+    }
+}
+
+/// Copying where enemies are from [CurrentPuzzle].
+pub fn follow_puzzle(
+    puzzle: Res<CurrentPuzzle>,
+    query: Query<(&EnemyFish, &mut TileCoords, &mut Transform)>,
+) {
+    for (enemy_fish, mut tile_coords, mut transform) in query {
+        if let Some(enemy) = puzzle.enemies().get(enemy_fish.index) {
+            tile_coords.tile_pos = enemy.pos();
+            transform.rotation = heading_to_rotation(enemy.heading());
+        }
+    }
+}
+
+pub fn swim_to_ideal_position(
+    time: Res<Time>,
+    query: Query<(&mut Transform, &TileCoords), With<EnemyFish>>,
+) {
+    let speed = TILE_SIZE as f32 / SECONDS_PER_TILE * time.delta_secs();
+    for (mut transform, tile_coords) in query {
+        let target = tile_coords.into_vec2();
+        let difference = target - transform.translation.xy();
+        if difference.length() <= speed {
+            transform.translation = target.extend(ENEMY_LAYER);
+        } else {
+            transform.translation += (difference.normalize() * speed).extend(0.);
+        }
+    }
+}
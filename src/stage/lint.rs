@@ -0,0 +1,304 @@
+//! Finding problems of a stage file without spawning anything.
+
+use super::{
+    LogiKind, Tile,
+    construction::{TileError, aquarium_size, interprint_aquarium},
+};
+use crate::{prelude::*, stage_manager::StageField};
+use bevy::math::IVec2;
+use serde::Deserialize;
+use toml::Spanned;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /// The stage works, but may not be as intended.
+    Warning,
+    /// The stage is broken.
+    Error,
+}
+
+/// A problem found in a stage file.
+///
+/// Both the line and the column count from 1.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, severity, self.message
+        )
+    }
+}
+
+/// The same fields as [ConstructAquarium], but with where they are in the file.
+#[derive(Deserialize)]
+struct SpannedAquarium {
+    content: Spanned<String>,
+    player_origin: Spanned<IVec2>,
+    player_defaultbits: Spanned<Vec<bool>>,
+    #[serde(default)]
+    goal_patterns: Vec<SpannedGoalPattern>,
+    #[serde(default)]
+    companions: Vec<SpannedCompanion>,
+    #[serde(default)]
+    enemies: Vec<SpannedEnemy>,
+    #[serde(default)]
+    clocks: Vec<SpannedClock>,
+}
+
+#[derive(Deserialize)]
+struct SpannedClock {
+    at: Spanned<IVec2>,
+}
+
+#[derive(Deserialize)]
+struct SpannedEnemy {
+    origin: Spanned<IVec2>,
+}
+
+#[derive(Deserialize)]
+struct SpannedCompanion {
+    origin: Spanned<IVec2>,
+    defaultbits: Spanned<Vec<bool>>,
+}
+
+#[derive(Deserialize)]
+struct SpannedGoalPattern {
+    at: Spanned<IVec2>,
+}
+
+/// Getting the line and the column of a byte offset, counting from 1.
+fn position_of(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|l| l.chars().count())
+        .unwrap_or(0)
+        + 1;
+    (line, column)
+}
+
+/// Getting the byte offset where the first line of a multi-line string starts.
+fn content_offset(source: &str, span_start: usize) -> usize {
+    let literal = &source[span_start..];
+    let quotes = if literal.starts_with("\"\"\"") || literal.starts_with("'''") {
+        3
+    } else {
+        1
+    };
+    // A newline just after the quotes is trimmed by TOML
+    let after_quotes = &literal[quotes..];
+    let newline = if after_quotes.starts_with("\r\n") {
+        2
+    } else if after_quotes.starts_with('\n') {
+        1
+    } else {
+        0
+    };
+    span_start + quotes + newline
+}
+
+/// Finding problems of a stage written in TOML.
+pub fn lint_stage(source: &str) -> Vec<Diagnostic> {
+    let diagnostic_at = |offset: usize, severity: Severity, message: String| {
+        let (line, column) = position_of(source, offset);
+        Diagnostic {
+            line,
+            column,
+            severity,
+            message,
+        }
+    };
+    let aquarium = match toml::from_str::<ConstructAquarium>(source) {
+        Ok(aquarium) => aquarium,
+        Err(e) => {
+            let offset = e.span().map(|s| s.start).unwrap_or(0);
+            return vec![diagnostic_at(
+                offset,
+                Severity::Error,
+                e.message().to_string(),
+            )];
+        }
+    };
+    let spanned = match toml::from_str::<SpannedAquarium>(source) {
+        Ok(spanned) => spanned,
+        Err(e) => return vec![diagnostic_at(0, Severity::Error, e.message().to_string())],
+    };
+
+    let mut diagnostics = Vec::new();
+    let content = &aquarium.content;
+    let (first_line, first_column) =
+        position_of(source, content_offset(source, spanned.content.span().start));
+    let size = aquarium_size(content);
+    // Converting a line and a column in the content into those in the file
+    let content_position = |line: usize, column: usize| {
+        let column = if line == 0 { first_column } else { 1 } + column;
+        (first_line + line, column)
+    };
+    let tile_position =
+        |pos: IVec2| content_position(size.y as usize - pos.y as usize - 1, pos.x as usize);
+
+    // Checking each charactor
+    let (tiles, problems) = interprint_aquarium(content);
+    for problem in problems {
+        let severity = match problem.error {
+            TileError::UnknownCharactor(_) => Severity::Warning,
+            _ => Severity::Error,
+        };
+        let (line, column) = content_position(problem.line, problem.column);
+        diagnostics.push(Diagnostic {
+            line,
+            column,
+            severity,
+            message: problem.error.to_string(),
+        });
+    }
+
+    // Checking the fields in the same way as loading the stage
+    for (field, error) in aquarium.problems(&tiles) {
+        let offset = match field {
+            StageField::Content => {
+                diagnostics.push(Diagnostic {
+                    line: first_line,
+                    column: first_column,
+                    severity: Severity::Error,
+                    message: error.to_string(),
+                });
+                continue;
+            }
+            StageField::PlayerOrigin => spanned.player_origin.span().start,
+            StageField::PlayerDefaultbits => spanned.player_defaultbits.span().start,
+            StageField::CompanionOrigin(i) => spanned.companions[i].origin.span().start,
+            StageField::CompanionDefaultbits(i) => spanned.companions[i].defaultbits.span().start,
+            StageField::Enemy(i) => spanned.enemies[i].origin.span().start,
+            StageField::GoalPattern(i) => spanned.goal_patterns[i].at.span().start,
+            StageField::Clock(i) => spanned.clocks[i].at.span().start,
+        };
+        diagnostics.push(diagnostic_at(offset, Severity::Error, error.to_string()));
+    }
+
+    // Checking the length of the register and each gate
+    let bit_count = aquarium.player_defaultbits.len();
+    let is_bit_at = |pos: IVec2| {
+        tiles
+            .iter()
+            .any(|(p, tile)| *p == pos && matches!(tile, Tile::GateBit { .. }))
+    };
+    for (pos, tile) in &tiles {
+        if let Tile::GateEnd {
+            logikind,
+            is_head: true,
+            direction,
+        } = tile
+        {
+            // Bits are on the left of a horizontal head, or above a vertical one
+            let toward_tail = match direction {
+                Direction::X => IVec2::new(-1, 0),
+                Direction::Y => IVec2::new(0, 1),
+            };
+            let length = (1..)
+                .take_while(|i| is_bit_at(*pos + toward_tail * *i))
+                .count();
+            if length != bit_count {
+                let (line, column) = tile_position(*pos + toward_tail * (length as i32 + 1));
+                diagnostics.push(Diagnostic {
+                    line,
+                    column,
+                    severity: Severity::Warning,
+                    message: format!(
+                        "the {:?} gate has {} bits, but player_defaultbits has {}",
+                        logikind, length, bit_count
+                    ),
+                });
+            }
+        }
+    }
+
+    // Checking doors can be opened
+    for (pos, tile) in &tiles {
+        if let Tile::Door { group } = tile
+            && !tiles
+                .iter()
+                .any(|(_, t)| *t == Tile::Plate { group: *group })
+        {
+            let (line, column) = tile_position(*pos);
+            diagnostics.push(Diagnostic {
+                line,
+                column,
+                severity: Severity::Warning,
+                message: format!(
+                    "the door '{}' never opens without any plate '{}'",
+                    group,
+                    group.to_ascii_lowercase()
+                ),
+            });
+        }
+    }
+
+    let has_adder = tiles.iter().any(|(_, t)| {
+        matches!(
+            t,
+            Tile::GateBit {
+                logikind: LogiKind::Add,
+                ..
+            }
+        )
+    });
+    for (pos, tile) in &tiles {
+        if *tile == Tile::OverflowDoor && !has_adder {
+            let (line, column) = tile_position(*pos);
+            diagnostics.push(Diagnostic {
+                line,
+                column,
+                severity: Severity::Warning,
+                message: "the overflow door never opens without any adder gate (+)".to_string(),
+            });
+        }
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AQUARIUM: &str = "stage_name = \"test\"\ncontent = \"\"\"\n     E\n  W   \n      \n\"\"\"\nplayer_origin = [2, 0]\nplayer_defaultbits = [false]\n";
+
+    #[test]
+    fn lint_rejects_what_loading_rejects() {
+        let broken = [
+            "[[companions]]\norigin = [9, 2]\ndefaultbits = [true]\n",
+            "[[companions]]\norigin = [5, 2]\ndefaultbits = []\n",
+            "[[companions]]\norigin = [5, 2]\ndefaultbits = [true]\n[[companions]]\norigin = [4, 2]\ndefaultbits = [true]\n",
+            "[[enemies]]\norigin = [2, 1]\n",
+            "[[enemies]]\norigin = [4, 2]\nheading = [2, 0]\n",
+        ];
+        for fields in broken {
+            let source = format!("{}{}", AQUARIUM, fields);
+            assert!(ConstructAquarium::from_toml(&source).is_err(), "{}", fields);
+            let errors = lint_stage(&source)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            assert_eq!(errors, 1, "{}", fields);
+        }
+        assert!(ConstructAquarium::from_toml(AQUARIUM).is_ok());
+        assert!(lint_stage(AQUARIUM).is_empty());
+    }
+}
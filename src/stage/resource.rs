@@ -4,6 +4,7 @@ const LOGIGATE_TILESET: &str = "embedded://tile/logical_gates.png";
 const OUTLINE_TILESET: &str = "embedded://tile/aquarium.png";
 const WALL_SPRITE: &str = "embedded://tile/wall.png";
 const GOAL_SPRITE: &str = "embedded://tile/goal.png";
+const FISH_SPRITE: &str = "embedded://boid/fish.png";
 
 #[derive(Resource, Default)]
 pub struct AquariumResource {
@@ -13,6 +14,7 @@ pub struct AquariumResource {
     pub outline_layout: Handle<TextureAtlasLayout>,
     pub wall_sprite: Handle<Image>,
     pub goal_sprite: Handle<Image>,
+    pub fish_sprite: Handle<Image>,
 }
 
 pub fn init_aquarium_resource(
@@ -38,4 +40,5 @@ pub fn init_aquarium_resource(
     ));
     resource.wall_sprite = asset_server.load(WALL_SPRITE);
     resource.goal_sprite = asset_server.load(GOAL_SPRITE);
+    resource.fish_sprite = asset_server.load(FISH_SPRITE);
 }
@@ -1,10 +1,8 @@
-use super::{Goal, IncorrectBit, LogiRegister, TILE_LAYER};
+use super::{ClockTick, Door, Goal, IncorrectBit, LogiRegister, Pearl, TILE_LAYER};
 use crate::prelude::*;
 use bevy::prelude::*;
 
-pub fn highlight_incorrect_bits(
-    query: Query<(&mut Sprite, &mut IncorrectBit), With<LogiRegister>>,
-) {
+pub fn highlight_incorrect_bits(query: Query<(&mut Sprite, &mut IncorrectBit)>) {
     for (mut sprite, mut incorrect_bit) in query {
         let not_red = 255 - incorrect_bit.remaining;
         sprite.color = Color::srgb_u8(255, not_red, not_red);
@@ -12,6 +10,92 @@ pub fn highlight_incorrect_bits(
     }
 }
 
+/// How opaque an open door is.
+const OPEN_DOOR_ALPHA: f32 = 0.2;
+
+/// Showing doors faintly while they're open.
+pub fn open_and_close_doors(
+    puzzle: Res<CurrentPuzzle>,
+    query: Query<(&mut Sprite, &TileCoords), With<Door>>,
+) {
+    let closed = puzzle.closed_doors();
+    for (mut sprite, tile_coords) in query {
+        let alpha = if closed.contains(&tile_coords.tile_pos) {
+            1.
+        } else {
+            OPEN_DOOR_ALPHA
+        };
+        sprite.color.set_alpha(alpha);
+    }
+}
+
+/// Hiding pearls which the boxfish picked up, and showing ones given back by undo.
+pub fn hide_picked_pearls(
+    puzzle: Res<CurrentPuzzle>,
+    query: Query<(&mut Visibility, &TileCoords), With<Pearl>>,
+) {
+    for (mut visibility, tile_coords) in query {
+        *visibility = if puzzle.pearls().contains(&tile_coords.tile_pos) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// How many secs a bit of a clock gate takes to flip.
+const CLOCK_TICK_SECONDS: f32 = 0.3;
+
+/// Showing bits of clock gates as they are in this turn,
+/// and flipping ones which have changed.
+pub fn flip_clock_bits(
+    mut commands: Commands,
+    puzzle: Res<CurrentPuzzle>,
+    query: Query<(Entity, &mut Sprite, &TileCoords), With<LogiRegister>>,
+) {
+    for (entity, mut sprite, tile_coords) in query {
+        let Some(gate) = puzzle
+            .layout()
+            .gates
+            .iter()
+            .find(|g| g.pos == tile_coords.tile_pos && g.clock.is_some())
+        else {
+            continue;
+        };
+        // The tile of 1 is left to the tile of 0
+        let index = if gate.boolean_at(puzzle.steps()) {
+            0
+        } else {
+            1
+        };
+        if let Some(atlas) = &mut sprite.texture_atlas
+            && atlas.index != index
+        {
+            atlas.index = index;
+            commands
+                .entity(entity)
+                .try_insert(ClockTick { progress: 0. });
+        }
+    }
+}
+
+/// Squashing a flipped bit then stretching it back, as if it turned over.
+pub fn clock_tick_animation(
+    mut commands: Commands,
+    query: Query<(Entity, &mut Transform, &mut ClockTick)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut tick) in query {
+        tick.progress += time.delta_secs() / CLOCK_TICK_SECONDS;
+        if tick.progress >= 1. {
+            transform.scale = Vec3::ONE;
+            commands.entity(entity).remove::<ClockTick>();
+        } else {
+            transform.scale.y = (tick.progress * std::f32::consts::PI).cos().abs();
+        }
+    }
+}
+
 pub fn goal_swaying(query: Query<(&mut Transform, &TileCoords), With<Goal>>, time: Res<Time>) {
     for (mut transform, tile_coords) in query {
         let swayness = Vec2::new((time.elapsed_secs() * 3.).sin(), time.elapsed_secs().sin())
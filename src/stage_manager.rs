@@ -1,13 +1,25 @@
+mod hot_reload;
+pub mod pack;
+
 use bevy::{
     audio::{PlaybackMode, Volume},
     prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, futures::check_ready},
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    MacroStates,
-    prelude::{Collidable, Collision, TileCoords},
-    stage::{ConstructionCompleted, SemiCollidable},
+    prelude::MacroStates,
+    progress::Progress,
+    puzzle::{
+        PuzzleState,
+        solver::{self, DEFAULT_STATE_LIMIT, Solution},
+    },
+    stage::{
+        Tile,
+        construction::{TileError, TileProblem, aquarium_size, interprint_aquarium},
+    },
+    stage_manager::pack::{PackStage, StagePack, discover_packs},
 };
 
 pub struct StageManagerPlugin;
@@ -17,26 +29,42 @@ impl Plugin for StageManagerPlugin {
         app.add_event::<ConstructAquarium>()
             .add_event::<NextStage>()
             .add_event::<NewGame>()
+            .add_event::<LoadStage>()
             .init_resource::<StageManager>()
-            .init_resource::<StageInfo>()
-            .add_systems(
-                Startup,
-                (setup_stage_manager, reset_into_first_stage).chain(),
-            )
+            .init_resource::<BrokenStage>()
+            .init_resource::<hot_reload::StageWatcher>()
+            .init_resource::<OptimalSteps>()
+            .add_systems(Startup, (setup_stage_manager, continue_on_startup).chain())
             .add_systems(
                 Update,
                 (
                     call_next_aquarium,
+                    load_stage.run_if(on_event::<LoadStage>),
+                    show_broken_stage
+                        .run_if(|b: Res<BrokenStage>| b.error.is_some())
+                        .run_if(in_state(MacroStates::GamePlay)),
+                    solve_every_stage.run_if(|o: Res<OptimalSteps>, s: Res<StageManager>| {
+                        o.solved_pack != Some(s.pack)
+                    }),
+                    receive_optimal_steps.run_if(|o: Res<OptimalSteps>| o.task.is_some()),
                     soundeffect_on_stage_loaded,
-                    reset_into_first_stage.run_if(on_event::<NewGame>),
+                    hot_reload::reload_modified_stage.run_if(in_state(MacroStates::GamePlay)),
+                    start_new_game.run_if(on_event::<NewGame>),
                 ),
-            )
-            .add_systems(PostUpdate, analyse_stage_collisions);
+            );
     }
 }
 
-#[derive(Event)]
-pub struct NewGame;
+#[derive(Event, Clone, Copy)]
+/// Starting the game over, from which stage is decided by the variant.
+pub enum NewGame {
+    /// Starting from the first stage.
+    FirstStage,
+    /// Starting from the first stage which isn't cleared yet.
+    Continue,
+    /// Starting from the given stage (e.g. for a replay).
+    Stage(usize),
+}
 
 #[derive(Event, Clone, Serialize, Deserialize)]
 pub struct ConstructAquarium {
@@ -44,45 +72,416 @@ pub struct ConstructAquarium {
     pub content: String,
     pub player_origin: IVec2,
     pub player_defaultbits: Vec<bool>,
+    /// Whether the boxfish turns toward where it moves.
+    /// Otherwise its body always trails on the left of the head.
+    #[serde(default)]
+    pub turnable: bool,
+    /// Goals which need the register to be a pattern.
+    /// Other goals accept any register.
+    #[serde(default)]
+    pub goal_patterns: Vec<GoalPattern>,
+    /// Other boxfish which the player can switch to.
+    /// The stage is cleared when every boxfish reached a goal.
+    #[serde(default)]
+    pub companions: Vec<Companion>,
+    #[serde(default)]
+    pub enemies: Vec<EnemyPatrol>,
+    /// How many steps the player can take in the stage.
+    /// Undo gives steps back, and it's unlimited when missing.
+    #[serde(default)]
+    pub max_steps: Option<u32>,
+    /// Gates whose bits flip by themselves as the boxfish moves.
+    #[serde(default)]
+    pub clocks: Vec<ClockGate>,
+}
+
+/// A logical gate whose bits flip every `period` steps.
+///
+/// The gate is found by its tail, and the bits are the ones written
+/// in the stage until `phase` steps have passed in the first period.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ClockGate {
+    pub at: IVec2,
+    pub period: u32,
+    #[serde(default)]
+    pub phase: u32,
+}
+
+/// A fish which swims a tile every time the boxfish moves,
+/// and catches the boxfish on touching it.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EnemyPatrol {
+    pub origin: IVec2,
+    /// Which way it swims, turning back at walls and gates.
+    #[serde(default)]
+    pub heading: IVec2,
+    /// Ways which it swims in order instead of heading,
+    /// back to the first after the last.
+    #[serde(default)]
+    pub path: Vec<IVec2>,
+}
+
+/// A boxfish other than the one controlled first.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Companion {
+    pub origin: IVec2,
+    pub defaultbits: Vec<bool>,
+}
+
+/// A goal tile which needs the register to be exactly the bits.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GoalPattern {
+    pub at: IVec2,
+    pub bits: Vec<bool>,
+}
+
+/// A reason why a stage couldn't be loaded.
+#[derive(Clone, Debug)]
+pub enum StageLoadError {
+    /// The stage file couldn't be read.
+    Io(String),
+    /// The stage is not written in TOML properly.
+    Syntax(String),
+    /// A field of [ConstructAquarium] is missing or has a wrong type.
+    Field(String),
+    /// A charactor of the aquarium couldn't be interprinted as a tile.
+    Tile(TileProblem),
+    /// The aquarium has no goal, so it can never be cleared.
+    NoGoal,
+    /// A boxfish is outside of the aquarium.
+    OutsideAquarium { origin: IVec2, size: UVec2 },
+    /// A part of a boxfish is inside a wall.
+    InsideWall { origin: IVec2, cell: IVec2 },
+    /// A boxfish is on another boxfish.
+    Overlapping(IVec2),
+    /// A boxfish has no register.
+    EmptyRegister(IVec2),
+    /// An enemy fish is inside a wall.
+    EnemyInWall(IVec2),
+    /// An enemy fish swims farther than a tile at once.
+    EnemyWay { origin: IVec2, way: IVec2 },
+    /// A pattern of goal_patterns is put where no goal is.
+    GoalPattern(IVec2),
+    /// A clock isn't at the tail of a gate.
+    ClockTail(IVec2),
+    /// A clock never ticks, or its phase is longer than its period.
+    ClockPeriod(IVec2),
+}
+
+/// Which field of a stage a [StageLoadError] is found in,
+/// with the index of the table in an array.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StageField {
+    Content,
+    PlayerOrigin,
+    PlayerDefaultbits,
+    CompanionOrigin(usize),
+    CompanionDefaultbits(usize),
+    Enemy(usize),
+    GoalPattern(usize),
+    Clock(usize),
+}
+
+impl std::fmt::Display for StageLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StageLoadError::Io(message) => write!(f, "{}", message),
+            StageLoadError::Syntax(message) => write!(f, "syntax error: {}", message),
+            StageLoadError::Field(message) => write!(f, "{}", message),
+            StageLoadError::Tile(problem) => write!(
+                f,
+                "line {}, column {} of the content: {}",
+                problem.line + 1,
+                problem.column + 1,
+                problem.error
+            ),
+            StageLoadError::NoGoal => write!(f, "the aquarium has no goal (E)"),
+            StageLoadError::OutsideAquarium { origin, size } => write!(
+                f,
+                "the boxfish at {} is outside of the aquarium sized {}",
+                origin, size
+            ),
+            StageLoadError::InsideWall { origin, cell } => {
+                write!(f, "the boxfish at {} is inside a wall at {}", origin, cell)
+            }
+            StageLoadError::Overlapping(origin) => {
+                write!(f, "the boxfish at {} is on another boxfish", origin)
+            }
+            StageLoadError::EmptyRegister(origin) => {
+                write!(f, "the boxfish at {} has no bit", origin)
+            }
+            StageLoadError::EnemyInWall(origin) => {
+                write!(f, "the enemy fish at {} is inside a wall", origin)
+            }
+            StageLoadError::EnemyWay { origin, way } => write!(
+                f,
+                "the enemy fish at {} swims {}, which isn't a tile",
+                origin, way
+            ),
+            StageLoadError::GoalPattern(at) => {
+                write!(
+                    f,
+                    "goal_patterns has a pattern at {}, but no goal is there",
+                    at
+                )
+            }
+            StageLoadError::ClockTail(at) => {
+                write!(f, "no gate's tail is at {} of clocks", at)
+            }
+            StageLoadError::ClockPeriod(at) => write!(
+                f,
+                "the clock at {} needs a period longer than its phase",
+                at
+            ),
+        }
+    }
+}
+
+impl ConstructAquarium {
+    /// Parsing a stage written in TOML, then checking that it's playable.
+    pub fn from_toml(source: &str) -> Result<Self, StageLoadError> {
+        let table = toml::from_str::<toml::Table>(source)
+            .map_err(|e| StageLoadError::Syntax(e.message().to_string()))?;
+        let aquarium = toml::Value::Table(table)
+            .try_into::<ConstructAquarium>()
+            .map_err(|e| StageLoadError::Field(e.message().to_string()))?;
+
+        let (tiles, problems) = interprint_aquarium(&aquarium.content);
+        // Unknown charactors are just ignored, so the stage is still playable
+        if let Some(problem) = problems
+            .into_iter()
+            .find(|p| !matches!(p.error, TileError::UnknownCharactor(_)))
+        {
+            return Err(StageLoadError::Tile(problem));
+        }
+        match aquarium.problems(&tiles).into_iter().next() {
+            Some((_, error)) => Err(error),
+            None => Ok(aquarium),
+        }
+    }
+
+    /// Finding every problem of the fields which makes the stage unplayable,
+    /// with the tiles interprinted from the content.
+    ///
+    /// Both loading and linting a stage check it with this.
+    pub fn problems(&self, tiles: &[(IVec2, Tile)]) -> Vec<(StageField, StageLoadError)> {
+        let mut problems = Vec::new();
+        if !tiles.iter().any(|(_, tile)| *tile == Tile::Goal) {
+            problems.push((StageField::Content, StageLoadError::NoGoal));
+        }
+
+        // Checking every boxfish, the player's one comes first
+        let size = aquarium_size(&self.content);
+        let puzzle = PuzzleState::from(self);
+        let walls = &puzzle.layout().walls;
+        let boxfish = std::iter::once((
+            StageField::PlayerOrigin,
+            StageField::PlayerDefaultbits,
+            self.player_origin,
+            &self.player_defaultbits,
+            puzzle.body_cells(),
+        ))
+        .chain(self.companions.iter().zip(puzzle.others()).enumerate().map(
+            |(i, (companion, other))| {
+                (
+                    StageField::CompanionOrigin(i),
+                    StageField::CompanionDefaultbits(i),
+                    companion.origin,
+                    &companion.defaultbits,
+                    other.body_cells(),
+                )
+            },
+        ))
+        .collect::<Vec<_>>();
+        for (i, (field, bits_field, origin, bits, body)) in boxfish.iter().enumerate() {
+            if bits.is_empty() {
+                problems.push((*bits_field, StageLoadError::EmptyRegister(*origin)));
+            }
+            let error = if origin.cmplt(IVec2::ZERO).any() || origin.cmpge(size.as_ivec2()).any() {
+                StageLoadError::OutsideAquarium {
+                    origin: *origin,
+                    size,
+                }
+            } else if let Some(cell) = body.iter().find(|cell| walls.contains(cell)) {
+                StageLoadError::InsideWall {
+                    origin: *origin,
+                    cell: *cell,
+                }
+            } else if boxfish[..i]
+                .iter()
+                .any(|(.., other)| body.iter().any(|cell| other.contains(cell)))
+            {
+                StageLoadError::Overlapping(*origin)
+            } else {
+                continue;
+            };
+            problems.push((*field, error));
+        }
+
+        for (i, patrol) in self.enemies.iter().enumerate() {
+            let ways = std::iter::once(patrol.heading).chain(patrol.path.iter().copied());
+            let error = if walls.contains(&patrol.origin) {
+                StageLoadError::EnemyInWall(patrol.origin)
+            } else if let Some(way) = ways
+                .filter(|w| *w != IVec2::ZERO)
+                .find(|w| w.abs().element_sum() != 1)
+            {
+                StageLoadError::EnemyWay {
+                    origin: patrol.origin,
+                    way,
+                }
+            } else {
+                continue;
+            };
+            problems.push((StageField::Enemy(i), error));
+        }
+
+        for (i, pattern) in self.goal_patterns.iter().enumerate() {
+            if !tiles.contains(&(pattern.at, Tile::Goal)) {
+                problems.push((
+                    StageField::GoalPattern(i),
+                    StageLoadError::GoalPattern(pattern.at),
+                ));
+            }
+        }
+
+        for (i, clock) in self.clocks.iter().enumerate() {
+            let error = if !tiles.iter().any(|(pos, tile)| {
+                *pos == clock.at && matches!(tile, Tile::GateEnd { is_head: false, .. })
+            }) {
+                StageLoadError::ClockTail(clock.at)
+            } else if clock.phase >= clock.period {
+                StageLoadError::ClockPeriod(clock.at)
+            } else {
+                continue;
+            };
+            problems.push((StageField::Clock(i), error));
+        }
+        problems
+    }
+}
+
+#[derive(Event)]
+/// Loading the stage at the index of [StageManager].
+///
+/// On success [ConstructAquarium] is called,
+/// otherwise the reason is kept in [BrokenStage].
+pub struct LoadStage {
+    pub index: usize,
+}
+
+#[derive(Resource, Default)]
+/// The last stage which couldn't be loaded.
+///
+/// It's shown to the player on [MacroStates::StageError].
+pub struct BrokenStage {
+    pub index: usize,
+    pub error: Option<StageLoadError>,
 }
 
 #[derive(Resource, Default)]
 pub struct StageManager {
-    pub stages: Vec<&'static str>,
+    /// Every available stage pack, the default one comes first.
+    pub packs: Vec<StagePack>,
+    /// The index of the pack being played.
+    pub pack: usize,
     pub index: usize,
+    /// The stage which the current game started from.
+    pub first_stage: usize,
     pub on_loaded_soundeffect: Handle<AudioSource>,
 }
 
-#[derive(Resource, Default)]
-pub struct StageInfo {
-    pub collisions: Collision,
-    pub semicollisions: Collision,
+impl StageManager {
+    /// The stages of the pack being played.
+    pub fn stages(&self) -> &[PackStage] {
+        self.packs
+            .get(self.pack)
+            .map(|pack| pack.stages.as_slice())
+            .unwrap_or_default()
+    }
 }
 
-const STAGE_0: &str = include_str!("../assets/stages/stage_0.toml");
-const STAGE_1: &str = include_str!("../assets/stages/stage_1.toml");
-const STAGE_2: &str = include_str!("../assets/stages/stage_2.toml");
-const STAGE_3: &str = include_str!("../assets/stages/stage_3.toml");
-const STAGE_4: &str = include_str!("../assets/stages/stage_4.toml");
-const STAGE_5: &str = include_str!("../assets/stages/stage_5.toml");
-const STAGE_6: &str = include_str!("../assets/stages/stage_6.toml");
-const STAGE_7: &str = include_str!("../assets/stages/stage_7.toml");
+#[derive(Resource, Default)]
+/// The fewest steps to clear every stage in total, found by the solver.
+///
+/// It's None while solving, or when any stage couldn't be solved,
+/// since the player's steps count every stage.
+pub struct OptimalSteps {
+    pub total: Option<u32>,
+    /// The index of the pack which [OptimalSteps::total] is for.
+    solved_pack: Option<usize>,
+    task: Option<Task<Option<u32>>>,
+}
 
 pub fn setup_stage_manager(mut stage: ResMut<StageManager>, asset_server: Res<AssetServer>) {
-    stage.stages = vec![
-        STAGE_0, STAGE_1, STAGE_2, STAGE_3, STAGE_4, STAGE_5, STAGE_6, STAGE_7,
-    ];
+    (stage.packs, stage.pack) = discover_packs();
     stage.on_loaded_soundeffect = asset_server.load("embedded://sound_effects/load_stage.ogg");
 }
 
-/// Reset [StageManager]'s index, then call [ConstructAquarium] event on the first stage.
-pub fn reset_into_first_stage(
-    mut stage: ResMut<StageManager>,
-    mut construct_stage: EventWriter<ConstructAquarium>,
+/// Continuing the game from the saved progress on launched.
+pub fn continue_on_startup(mut new_game: EventWriter<NewGame>) {
+    new_game.write(NewGame::Continue);
+}
+
+/// Reset [StageManager]'s index, then call [LoadStage] event on the stage to start from.
+pub fn start_new_game(
+    mut stage_manager: ResMut<StageManager>,
+    progress: Res<Progress>,
+    mut new_game: EventReader<NewGame>,
+    mut load_stage: EventWriter<LoadStage>,
+) {
+    let Some(new_game) = new_game.read().last() else {
+        return;
+    };
+    let index = match *new_game {
+        NewGame::FirstStage => 0,
+        NewGame::Continue => match stage_manager.packs.get(stage_manager.pack) {
+            Some(pack) => progress.first_uncleared(&pack.title, pack.stages.len()),
+            None => 0,
+        },
+        NewGame::Stage(index) => index,
+    };
+    stage_manager.index = index;
+    stage_manager.first_stage = index;
+    load_stage.write(LoadStage { index });
+}
+
+/// Parsing the stage of [LoadStage], then call [ConstructAquarium] event on it.
+///
+/// A broken stage is kept in [BrokenStage] instead of being constructed.
+pub fn load_stage(
+    mut stage_manager: ResMut<StageManager>,
+    mut broken_stage: ResMut<BrokenStage>,
+    mut load_stage: EventReader<LoadStage>,
+    mut construct_aquarium: EventWriter<ConstructAquarium>,
 ) {
-    stage.index = 0;
-    construct_stage
-        .write(toml::from_str(stage.stages.first().unwrap()).expect("Stage 0 is broken!"));
+    // Only the latest request matters
+    let Some(&LoadStage { index }) = load_stage.read().last() else {
+        return;
+    };
+    let Some(stage) = stage_manager.stages().get(index) else {
+        return;
+    };
+    match stage
+        .read()
+        .and_then(|source| ConstructAquarium::from_toml(&source))
+    {
+        Ok(aquarium) => {
+            broken_stage.error = None;
+            construct_aquarium.write(aquarium);
+        }
+        Err(error) => {
+            warn!("Stage {} couldn't be loaded: {}", index + 1, error);
+            broken_stage.index = index;
+            broken_stage.error = Some(error);
+        }
+    }
+    stage_manager.index = index;
+}
+
+/// Showing a broken stage to the player instead of the gameplay.
+pub fn show_broken_stage(mut state: ResMut<NextState<MacroStates>>) {
+    state.set(MacroStates::StageError);
 }
 
 #[derive(Event)]
@@ -91,18 +490,16 @@ pub struct NextStage;
 
 /// Loading next aquarium, if it doesn't exist, [MacroStates] will be set GameClear.
 pub fn call_next_aquarium(
-    mut stage_manager: ResMut<StageManager>,
-    mut construct_aquarium: EventWriter<ConstructAquarium>,
+    stage_manager: Res<StageManager>,
+    mut load_stage: EventWriter<LoadStage>,
     mut next_stage: EventReader<NextStage>,
     mut state: ResMut<NextState<MacroStates>>,
 ) {
     for _ in next_stage.read() {
-        match stage_manager.stages.get(stage_manager.index + 1) {
-            Some(next_stage) => {
-                construct_aquarium.write(
-                    toml::from_str(next_stage).expect("The format of a aquarium is not satisfied!"),
-                );
-                stage_manager.index += 1;
+        let index = stage_manager.index + 1;
+        match stage_manager.stages().get(index) {
+            Some(_) => {
+                load_stage.write(LoadStage { index });
             }
             None => {
                 state.set(MacroStates::GameClear);
@@ -129,25 +526,46 @@ pub fn soundeffect_on_stage_loaded(
     }
 }
 
-/// Reading a new stage, then regist [Collidable] and [SemiCollidable] into [StageInfo]
-pub fn analyse_stage_collisions(
-    mut stage_info: ResMut<StageInfo>,
-    mut construction_completed: EventReader<ConstructionCompleted>,
-    collisions: Query<&TileCoords, With<Collidable>>,
-    semicollisions: Query<&TileCoords, With<SemiCollidable>>,
-) {
-    for _ in construction_completed.read() {
-        stage_info.collisions = Collision::from(
-            collisions
-                .iter()
-                .map(|c| c.tile_pos)
-                .collect::<Vec<IVec2>>(),
-        );
-        stage_info.semicollisions = Collision::from(
-            semicollisions
-                .iter()
-                .map(|c| c.tile_pos)
-                .collect::<Vec<IVec2>>(),
-        );
+/// Start solving every stage of the pack being played in background, for deciding ranks.
+pub fn solve_every_stage(stage_manager: Res<StageManager>, mut optimal: ResMut<OptimalSteps>) {
+    let stages = stage_manager.stages().to_vec();
+    optimal.total = None;
+    optimal.solved_pack = Some(stage_manager.pack);
+    optimal.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        let solved = stages
+            .iter()
+            .enumerate()
+            .map(|(i, stage)| {
+                let aquarium = stage
+                    .read()
+                    .and_then(|source| ConstructAquarium::from_toml(&source));
+                let solution = match aquarium {
+                    Ok(aquarium) => {
+                        solver::solve(&PuzzleState::from(&aquarium), DEFAULT_STATE_LIMIT)
+                    }
+                    Err(e) => {
+                        warn!("Stage {} can't be ranked against: {}", i + 1, e);
+                        return None;
+                    }
+                };
+                let reason = match solution {
+                    Solution::Found(_) => return solution.steps(),
+                    Solution::Unsolvable => "it can't be cleared",
+                    Solution::GaveUp => "the solver gave up",
+                };
+                warn!("Stage {} can't be ranked against: {}", i + 1, reason);
+                None
+            })
+            // Solving every stage to warn about all unsolved ones
+            .collect::<Vec<Option<u32>>>();
+        solved.into_iter().sum()
+    }));
+}
+
+/// Receiving the result of [solve_every_stage] when it finished.
+pub fn receive_optimal_steps(mut optimal: ResMut<OptimalSteps>) {
+    if let Some(total) = optimal.task.as_mut().and_then(check_ready) {
+        optimal.total = total;
+        optimal.task = None;
     }
 }
@@ -0,0 +1,81 @@
+//! Reloading the stage being played when its file is saved,
+//! so that stages can be designed with a text editor.
+
+use bevy::prelude::*;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::{ConstructAquarium, OptimalSteps, StageManager, pack::PackStage};
+
+/// How many secs between checking the stage file.
+const WATCH_INTERVAL: f32 = 0.5;
+
+#[derive(Resource)]
+/// The stage file being watched, and when it was modified at last.
+pub struct StageWatcher {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+impl Default for StageWatcher {
+    fn default() -> Self {
+        Self {
+            path: None,
+            modified: None,
+            timer: Timer::from_seconds(WATCH_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Calling [ConstructAquarium] event again when the file of the current stage was modified.
+///
+/// Embedded stages are never watched. When the modified stage is broken,
+/// the aquarium is kept as it is until the file is fixed.
+pub fn reload_modified_stage(
+    time: Res<Time>,
+    stage_manager: Res<StageManager>,
+    mut watcher: ResMut<StageWatcher>,
+    mut optimal: ResMut<OptimalSteps>,
+    mut construct_aquarium: EventWriter<ConstructAquarium>,
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let path = match stage_manager.stages().get(stage_manager.index) {
+        Some(PackStage::File(path)) => path,
+        _ => {
+            watcher.path = None;
+            return;
+        }
+    };
+    let modified = modified_time(path);
+    // Another stage was loaded, so start watching it
+    if watcher.path.as_ref() != Some(path) {
+        watcher.path = Some(path.clone());
+        watcher.modified = modified;
+        return;
+    }
+    if modified == watcher.modified {
+        return;
+    }
+    watcher.modified = modified;
+    // The ranks should be decided by the stage as it's now
+    optimal.solved_pack = None;
+    let aquarium = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| ConstructAquarium::from_toml(&source).map_err(|e| e.to_string()));
+    match aquarium {
+        Ok(aquarium) => {
+            info!("Reloaded {}", path.display());
+            construct_aquarium.write(aquarium);
+        }
+        Err(e) => warn!("{} couldn't be reloaded: {}", path.display(), e),
+    }
+}
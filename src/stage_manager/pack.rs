@@ -0,0 +1,188 @@
+//! Stage packs, which are directories with a `pack.toml` like this:
+//!
+//! ```toml
+//! title = "My Aquariums"
+//! author = "someone"
+//! stages = ["first.toml", "second.toml"]
+//! ```
+//!
+//! Stages are played in the order of `stages`,
+//! and their paths are relative to the directory.
+
+use bevy::log::warn;
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use super::StageLoadError;
+use crate::{arguments::path_argument, user_data::user_data_dir};
+
+/// The file name of the manifest of a stage pack.
+pub const MANIFEST_NAME: &str = "pack.toml";
+
+/// The directory in the user data directory where stage packs are put.
+const PACK_DIRECTORY: &str = "packs";
+
+/// Embedding a stage of the default pack with its file name.
+macro_rules! default_stage {
+    ($name:literal) => {
+        ($name, include_str!(concat!("../../assets/stages/", $name)))
+    };
+}
+
+const DEFAULT_MANIFEST: &str = include_str!("../../assets/stages/pack.toml");
+/// Stages of the default pack, which is embedded into the game.
+const DEFAULT_STAGES: [(&str, &str); 8] = [
+    default_stage!("stage_0.toml"),
+    default_stage!("stage_1.toml"),
+    default_stage!("stage_2.toml"),
+    default_stage!("stage_3.toml"),
+    default_stage!("stage_4.toml"),
+    default_stage!("stage_5.toml"),
+    default_stage!("stage_6.toml"),
+    default_stage!("stage_7.toml"),
+];
+
+/// The contents of `pack.toml`.
+#[derive(Deserialize)]
+pub struct PackManifest {
+    pub title: String,
+    pub author: String,
+    pub stages: Vec<PathBuf>,
+}
+
+/// A reason why a stage pack couldn't be read.
+#[derive(Debug)]
+pub enum PackError {
+    /// The manifest couldn't be read.
+    Io(std::io::Error),
+    /// The manifest is not written properly.
+    Manifest(toml::de::Error),
+}
+
+impl std::fmt::Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackError::Io(e) => write!(f, "couldn't read {}: {}", MANIFEST_NAME, e),
+            PackError::Manifest(e) => write!(f, "{}: {}", MANIFEST_NAME, e.message()),
+        }
+    }
+}
+
+/// Where a stage of a pack is kept.
+#[derive(Clone, Debug)]
+pub enum PackStage {
+    /// A stage embedded into the game.
+    Embedded(&'static str),
+    /// A stage file on a disk, which is read on every loading.
+    File(PathBuf),
+}
+
+impl PackStage {
+    /// Reading the stage written in TOML.
+    pub fn read(&self) -> Result<Cow<'static, str>, StageLoadError> {
+        match self {
+            PackStage::Embedded(source) => Ok(Cow::Borrowed(source)),
+            PackStage::File(path) => std::fs::read_to_string(path)
+                .map(Cow::Owned)
+                .map_err(|e| StageLoadError::Io(format!("{}: {}", path.display(), e))),
+        }
+    }
+}
+
+/// An ordered list of stages with its title and author.
+#[derive(Clone, Debug)]
+pub struct StagePack {
+    pub title: String,
+    pub author: String,
+    pub stages: Vec<PackStage>,
+}
+
+impl StagePack {
+    /// The pack of the built-in stages.
+    pub fn embedded() -> Self {
+        let manifest = toml::from_str::<PackManifest>(DEFAULT_MANIFEST)
+            .expect("The manifest of the default pack is broken!");
+        let stages = manifest
+            .stages
+            .iter()
+            .map(|name| {
+                DEFAULT_STAGES
+                    .iter()
+                    .find(|(n, _)| Path::new(n) == name)
+                    .map(|(_, source)| PackStage::Embedded(source))
+                    .expect("A stage of the default pack is not embedded!")
+            })
+            .collect();
+        Self {
+            title: manifest.title,
+            author: manifest.author,
+            stages,
+        }
+    }
+
+    /// Reading a pack from a directory which has `pack.toml`.
+    ///
+    /// Stage files themselves are not read here.
+    pub fn load(directory: &Path) -> Result<Self, PackError> {
+        let manifest =
+            std::fs::read_to_string(directory.join(MANIFEST_NAME)).map_err(PackError::Io)?;
+        let manifest = toml::from_str::<PackManifest>(&manifest).map_err(PackError::Manifest)?;
+        Ok(Self {
+            title: manifest.title,
+            author: manifest.author,
+            stages: manifest
+                .stages
+                .into_iter()
+                .map(|stage| PackStage::File(directory.join(stage)))
+                .collect(),
+        })
+    }
+}
+
+/// Collecting every pack in the user data directory, sorted by directory names.
+fn packs_in_user_data() -> Vec<StagePack> {
+    let Some(directory) = user_data_dir().map(|d| d.join(PACK_DIRECTORY)) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&directory) else {
+        return Vec::new();
+    };
+    let mut directories = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.join(MANIFEST_NAME).is_file())
+        .collect::<Vec<PathBuf>>();
+    directories.sort();
+    directories
+        .into_iter()
+        .filter_map(|path| match StagePack::load(&path) {
+            Ok(pack) => Some(pack),
+            Err(e) => {
+                warn!("The pack at {} is skipped: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Finding every available pack, and which of them should be played first.
+///
+/// The default pack always comes first. The pack given by `--pack`
+/// comes last, and is chosen if it could be read.
+pub fn discover_packs() -> (Vec<StagePack>, usize) {
+    let mut packs = vec![StagePack::embedded()];
+    packs.extend(packs_in_user_data());
+    let mut selected = 0;
+    if let Some(directory) = path_argument("--pack") {
+        match StagePack::load(&directory) {
+            Ok(pack) => {
+                packs.push(pack);
+                selected = packs.len() - 1;
+            }
+            Err(e) => warn!("The pack at {} is skipped: {}", directory.display(), e),
+        }
+    }
+    (packs, selected)
+}
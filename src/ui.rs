@@ -1,9 +1,12 @@
 mod esc_menu;
 mod game_clear;
+mod hint;
 mod operation_hint;
+mod out_of_moves;
 mod reset_exit_hint;
+mod stage_error;
 
-use crate::prelude::*;
+use crate::{prelude::*, replay::ReplayPlayback};
 use bevy::{audio::PlaybackMode, prelude::*};
 
 pub struct UIPlugin;
@@ -13,6 +16,7 @@ const PERCENT_PER_PIXEL: f32 = 6. / 32.;
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<UIResource>()
+            .init_resource::<hint::HintSolver>()
             .add_systems(Startup, init_ucr)
             .add_systems(PostStartup, reset_exit_hint::upper_left_menu_construction)
             .add_systems(
@@ -21,21 +25,41 @@ impl Plugin for UIPlugin {
             )
             .add_systems(
                 OnEnter(MacroStates::GamePlay),
-                operation_hint::construct_operation_hint.after(init_ucr),
+                (
+                    operation_hint::construct_operation_hint,
+                    hint::construct_hint_message,
+                )
+                    .after(init_ucr),
             )
             .add_systems(
                 OnEnter(MacroStates::GameClear),
                 game_clear::result_menu_construction,
             )
+            .add_systems(
+                OnEnter(MacroStates::StageError),
+                stage_error::stage_error_panel_construction,
+            )
+            .add_systems(
+                OnEnter(MacroStates::OutOfMoves),
+                out_of_moves::out_of_moves_panel_construction,
+            )
             .add_systems(
                 Update,
-                (reset_exit_hint::countup_reset_duration,).run_if(in_state(MacroStates::GamePlay)),
+                (
+                    (reset_exit_hint::countup_reset_duration, hint::request_hint)
+                        .run_if(|p: Res<ReplayPlayback>| !p.is_playing()),
+                    hint::show_hint,
+                    hint::clear_hint.run_if(resource_changed::<CurrentPuzzle>),
+                )
+                    .run_if(in_state(MacroStates::GamePlay)),
             )
             .add_systems(
                 Update,
                 (
                     esc_menu::on_quit_button_clicked,
                     esc_menu::on_start_button_clicked,
+                    esc_menu::on_pack_button_clicked,
+                    esc_menu::on_first_stage_button_clicked,
                     esc_menu::button_sounds,
                 )
                     .run_if(in_state(MacroStates::ESCMenu)),
@@ -44,7 +68,28 @@ impl Plugin for UIPlugin {
                 Update,
                 game_clear::return_to_main_menu_button.run_if(in_state(MacroStates::GameClear)),
             )
-            .add_systems(Update, (reset_exit_hint::stage_index_display, toggle_menu));
+            .add_systems(
+                Update,
+                (
+                    stage_error::skip_stage_button,
+                    stage_error::return_to_title_button,
+                )
+                    .run_if(in_state(MacroStates::StageError)),
+            )
+            .add_systems(
+                Update,
+                (out_of_moves::undo_button, out_of_moves::restart_button)
+                    .run_if(in_state(MacroStates::OutOfMoves)),
+            )
+            .add_systems(
+                Update,
+                (
+                    reset_exit_hint::stage_index_display,
+                    reset_exit_hint::remaining_steps_display,
+                    reset_exit_hint::pearl_count_display,
+                    toggle_menu,
+                ),
+            );
     }
 }
 
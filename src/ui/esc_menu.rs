@@ -11,15 +11,34 @@ pub struct StartButton;
 #[derive(Component)]
 pub struct EndGameButton;
 
+#[derive(Component)]
+/// Starting the pack from the first stage, even if it was cleared.
+pub struct FirstStageButton;
+
+#[derive(Component)]
+/// Switching to the next stage pack, shown only when there are some packs.
+pub struct PackButton;
+
+/// The text on [PackButton] for the pack being played.
+fn pack_button_text(stage_manager: &StageManager) -> String {
+    match stage_manager.packs.get(stage_manager.pack) {
+        Some(pack) => format!("パック：{}（{}）", pack.title, pack.author),
+        None => String::new(),
+    }
+}
+
 /// Constructs the menu shown when ESC key pressed on gameplayv, with those parts:
 ///
 /// - The game logo
 /// - Start Game
+/// - Start from the first stage
+/// - Stage Pack (when there are some packs)
 /// - Quit Game
 pub fn construct_esc_menu(
     mut commands: Commands,
     ucr: Res<UIResource>,
     asset_server: Res<AssetServer>,
+    stage_manager: Res<StageManager>,
 ) {
     let menu_font = TextFont {
         font: ucr.font.clone(),
@@ -65,6 +84,24 @@ pub fn construct_esc_menu(
                     Text::new("ハジメル"),
                     menu_font.clone(),
                 ))
+                .with_child((
+                    Button,
+                    FirstStageButton,
+                    TextColor::BLACK,
+                    Text::new("サイショから"),
+                    ucr.text_font.clone(),
+                ))
+                .with_children(|parent| {
+                    if stage_manager.packs.len() > 1 {
+                        parent.spawn((
+                            Button,
+                            PackButton,
+                            TextColor::BLACK,
+                            Text::new(pack_button_text(&stage_manager)),
+                            ucr.text_font.clone(),
+                        ));
+                    }
+                })
                 .with_child((
                     Button,
                     EndGameButton,
@@ -86,6 +123,38 @@ pub fn on_start_button_clicked(
     }
 }
 
+/// Starting the game over from the first stage.
+pub fn on_first_stage_button_clicked(
+    query: Query<&Interaction, (Changed<Interaction>, With<FirstStageButton>)>,
+    mut new_game: EventWriter<NewGame>,
+    mut macro_state: ResMut<NextState<MacroStates>>,
+) {
+    for i in query {
+        if *i == Interaction::Pressed {
+            new_game.write(NewGame::FirstStage);
+            macro_state.set(MacroStates::GamePlay);
+        }
+    }
+}
+
+/// Switching to the next pack, then continuing it from the first stage not cleared yet.
+pub fn on_pack_button_clicked(
+    query: Query<&Interaction, (Changed<Interaction>, With<PackButton>)>,
+    mut text_query: Query<&mut Text, With<PackButton>>,
+    mut stage_manager: ResMut<StageManager>,
+    mut new_game: EventWriter<NewGame>,
+) {
+    for i in query {
+        if *i == Interaction::Pressed {
+            stage_manager.pack = (stage_manager.pack + 1) % stage_manager.packs.len();
+            new_game.write(NewGame::Continue);
+            for mut text in &mut text_query {
+                text.0 = pack_button_text(&stage_manager);
+            }
+        }
+    }
+}
+
 pub fn on_quit_button_clicked(
     mut app_exit: EventWriter<AppExit>,
     query: Query<&Interaction, (Changed<Interaction>, With<EndGameButton>)>,
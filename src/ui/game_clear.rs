@@ -1,11 +1,17 @@
 use crate::prelude::*;
 use bevy::prelude::*;
 
-use crate::{boxfish::ResultManager, ui::UIResource};
+use crate::{
+    boxfish::ResultManager, progress::Progress, stage_manager::OptimalSteps, ui::UIResource,
+};
 
-const S_RANK_MAX: u32 = 400;
-const A_RANK_MAX: u32 = 450;
-const B_RANK_MAX: u32 = 500;
+// Ranks are decided by how many times as many steps
+// as the optimal solution the player took.
+// The built-in pack takes 167 steps at best, so those ratios were tuned
+// to keep its old borders of 400, 450 and 500 steps.
+const S_RANK_MAX: f32 = 2.4;
+const A_RANK_MAX: f32 = 2.7;
+const B_RANK_MAX: f32 = 3.0;
 
 #[derive(Component)]
 /// Reference [return_to_main_menu_button]
@@ -15,23 +21,48 @@ pub struct ReturnToMainMenuButton;
 /// Constructing a result menu with those parts:
 ///
 /// - Steps
+/// - Pearls
 /// - Rank
 /// - Prize (Originally, it was a project for my school festival)
 /// - Back to main screen
 pub fn result_menu_construction(
     mut commands: Commands,
     result_manager: Res<ResultManager>,
+    optimal_steps: Res<OptimalSteps>,
+    progress: Res<Progress>,
+    stage_manager: Res<StageManager>,
     ucr: Res<UIResource>,
 ) {
-    let steps = result_manager.steps;
-    let (rank, prize) = if steps < S_RANK_MAX {
-        ("S", "カントリーマアム2枚獲得！")
-    } else if steps < A_RANK_MAX {
-        ("A", "カントリーマアム1枚獲得！")
-    } else if steps < B_RANK_MAX {
-        ("B", "チョコ2個獲得！")
-    } else {
-        ("C", "チョコ1個獲得！")
+    // Stages skipped by continuing are counted by their best steps
+    let skipped_steps: u32 = match stage_manager.packs.get(stage_manager.pack) {
+        Some(pack) => (0..stage_manager.first_stage)
+            .filter_map(|i| progress.best_steps(&pack.title, i))
+            .sum(),
+        None => 0,
+    };
+    let steps = result_manager.steps + skipped_steps;
+    let skipped_pearls: u32 = match stage_manager.packs.get(stage_manager.pack) {
+        Some(pack) => (0..stage_manager.first_stage)
+            .filter_map(|i| progress.most_pearls(&pack.title, i))
+            .sum(),
+        None => 0,
+    };
+    let pearls = result_manager.pearls + skipped_pearls;
+    let (rank, prize) = match optimal_steps.total {
+        // Ranking is impossible while solving, or when a stage isn't solved
+        None => ("-", ""),
+        Some(optimal) => {
+            let ratio = steps as f32 / optimal as f32;
+            if ratio < S_RANK_MAX {
+                ("S", "カントリーマアム2枚獲得！")
+            } else if ratio < A_RANK_MAX {
+                ("A", "カントリーマアム1枚獲得！")
+            } else if ratio < B_RANK_MAX {
+                ("B", "チョコ2個獲得！")
+            } else {
+                ("C", "チョコ1個獲得！")
+            }
+        }
     };
     commands
         .spawn((
@@ -55,6 +86,15 @@ pub fn result_menu_construction(
                 ..default()
             },
         ))
+        .with_child((
+            Text::new(format!("シンジュ：{}", pearls)),
+            TextColor::WHITE,
+            TextFont {
+                font: ucr.font.clone(),
+                font_size: 48.,
+                ..default()
+            },
+        ))
         .with_child((
             Text::new(format!("ランク：{}", rank)),
             TextColor::WHITE,
@@ -95,7 +135,7 @@ pub fn return_to_main_menu_button(
 ) {
     for i in query {
         if *i == Interaction::Pressed {
-            construct_stage.write(NewGame);
+            construct_stage.write(NewGame::FirstStage);
             state.set(MacroStates::ESCMenu);
         }
     }
@@ -0,0 +1,145 @@
+use super::UIResource;
+use crate::{
+    prelude::*,
+    puzzle::solver::{self, DEFAULT_STATE_LIMIT, Solution},
+};
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, futures::check_ready},
+};
+
+/// Hints are shown over the boxfish.
+const HINT_LAYER: f32 = PLAYER_LAYER + 1.;
+
+#[derive(Resource, Default)]
+/// Holding the solver running for a hint.
+pub struct HintSolver {
+    task: Option<Task<Solution>>,
+}
+
+#[derive(Component)]
+/// A mark shown over the aquarium as a hint.
+///
+/// It disappears when the boxfish moved.
+pub struct HintMarker;
+
+#[derive(Component)]
+pub struct HintMessage;
+
+/// Constructing a message of hints on the bottom of a screen.
+pub fn construct_hint_message(mut commands: Commands, ucr: Res<UIResource>) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                align_items: AlignItems::FlexEnd,
+                justify_content: JustifyContent::Center,
+                padding: UiRect::all(Val::Vw(3.)),
+                ..default()
+            },
+            StateScoped(MacroStates::GamePlay),
+        ))
+        .with_child((
+            Text::new(String::new()),
+            TextColor::BLACK,
+            ucr.text_font.clone(),
+            HintMessage,
+        ));
+}
+
+/// Start solving from the current state when H on keyboard
+/// or West button on gamepad pressed.
+pub fn request_hint(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad: Query<&Gamepad>,
+    puzzle: Res<CurrentPuzzle>,
+    mut hint_solver: ResMut<HintSolver>,
+    mut message: Query<&mut Text, With<HintMessage>>,
+) {
+    let pressed = match gamepad.single() {
+        Ok(gamepad) => gamepad.just_pressed(GamepadButton::West),
+        Err(_) => false,
+    } | keyboard_input.just_pressed(KeyCode::KeyH);
+    if !pressed || hint_solver.task.is_some() {
+        return;
+    }
+    let state = puzzle.0.clone();
+    hint_solver.task = Some(
+        AsyncComputeTaskPool::get()
+            .spawn(async move { solver::solve(&state, DEFAULT_STATE_LIMIT) }),
+    );
+    for mut text in &mut message {
+        text.0 = "カンガエチュウ...".to_string();
+    }
+}
+
+/// Showing the next action of the optimal solution when the solver finished.
+pub fn show_hint(
+    mut commands: Commands,
+    mut hint_solver: ResMut<HintSolver>,
+    puzzle: Res<CurrentPuzzle>,
+    ucr: Res<UIResource>,
+    mut message: Query<&mut Text, With<HintMessage>>,
+) {
+    let Some(solution) = hint_solver.task.as_mut().and_then(check_ready) else {
+        return;
+    };
+    hint_solver.task = None;
+    let text = match solution {
+        Solution::Found(actions) => {
+            // An arrow for moving, a word for the other actions
+            let mark = match actions.first() {
+                Some(Action::Move(travel)) => Some((
+                    match (&travel.direction, travel.amount > 0) {
+                        (Direction::X, true) => "→",
+                        (Direction::X, false) => "←",
+                        (Direction::Y, true) => "↑",
+                        (Direction::Y, false) => "↓",
+                    },
+                    puzzle.head() + travel.into_ivec2(),
+                )),
+                Some(Action::Expand) => Some(("ノビル", puzzle.head() + IVec2::Y)),
+                Some(Action::Shrink) => Some(("チヂム", puzzle.head() + IVec2::Y)),
+                Some(Action::Switch) => Some(("コウタイ", puzzle.head() + IVec2::Y)),
+                None => None,
+            };
+            if let Some((mark, pos)) = mark {
+                commands.spawn((
+                    Text2d::new(mark),
+                    TextFont {
+                        font: ucr.font.clone(),
+                        font_size: TILE_SIZE as f32,
+                        ..default()
+                    },
+                    TextColor(Color::srgb_u8(220, 40, 40)),
+                    Transform::from_translation(TileCoords::ivec2_to_vec2(pos).extend(HINT_LAYER)),
+                    HintMarker,
+                    StateScoped(MacroStates::GamePlay),
+                ));
+            }
+            String::new()
+        }
+        Solution::Unsolvable => "もうゴールできない…Ctrl+Zでモドロウ".to_string(),
+        Solution::GaveUp => "ヒントが見つからなかった…".to_string(),
+    };
+    for mut message in &mut message {
+        message.0 = text.clone();
+    }
+}
+
+/// Clearing hints when the boxfish was changed, since they are out of date.
+pub fn clear_hint(
+    mut commands: Commands,
+    markers: Query<Entity, With<HintMarker>>,
+    mut hint_solver: ResMut<HintSolver>,
+    mut message: Query<&mut Text, With<HintMessage>>,
+) {
+    for entity in markers {
+        commands.entity(entity).despawn();
+    }
+    hint_solver.task = None;
+    for mut text in &mut message {
+        text.0 = String::new();
+    }
+}
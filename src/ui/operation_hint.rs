@@ -9,6 +9,8 @@ use bevy::prelude::*;
 /// - WASD icon
 /// - How to expand
 /// - Enter icon
+/// - How to get a hint
+/// - How to undo and redo
 pub fn construct_operation_hint(
     mut commands: Commands,
     ucr: Res<UIResource>,
@@ -63,6 +65,21 @@ pub fn construct_operation_hint(
                         width: Val::Vw(PERCENT_PER_PIXEL * 32.),
                         ..default()
                     },
+                ))
+                .with_child((
+                    Text::new("ヒント：H".to_string()),
+                    TextColor::BLACK,
+                    ucr.text_font.clone(),
+                ))
+                .with_child((
+                    Text::new("モドス：Ctrl+Z".to_string()),
+                    TextColor::BLACK,
+                    ucr.text_font.clone(),
+                ))
+                .with_child((
+                    Text::new("ヤリナオス：Ctrl+Y".to_string()),
+                    TextColor::BLACK,
+                    ucr.text_font.clone(),
                 ));
         });
 }
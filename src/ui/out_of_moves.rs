@@ -0,0 +1,97 @@
+use super::UIResource;
+use crate::{
+    boxfish::movement::snap_to_puzzle,
+    prelude::*,
+    replay::{Operated, Operation},
+    stage_manager::LoadStage,
+};
+use bevy::prelude::*;
+
+#[derive(Component)]
+/// Taking back the last move, which gives a step back.
+pub struct UndoButton;
+
+#[derive(Component)]
+/// Starting the stage over from the beginning.
+pub struct RestartButton;
+
+/// Constructing a panel with those parts:
+///
+/// - No steps are left
+/// - Undo the last move
+/// - Restart the stage
+pub fn out_of_moves_panel_construction(mut commands: Commands, ucr: Res<UIResource>) {
+    let message_font = TextFont {
+        font: ucr.font.clone(),
+        font_size: 48.,
+        ..default()
+    };
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Vw(1.),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0., 0., 0., 0.7)),
+            StateScoped(MacroStates::OutOfMoves),
+        ))
+        .with_child((
+            Text::new("テスウをツカイキッタ…"),
+            TextColor::WHITE,
+            message_font.clone(),
+        ))
+        .with_child((
+            Text::new("ヒトツモドス"),
+            TextColor::WHITE,
+            message_font.clone(),
+            Button,
+            UndoButton,
+        ))
+        .with_child((
+            Text::new("サイショから"),
+            TextColor::WHITE,
+            message_font,
+            Button,
+            RestartButton,
+        ));
+}
+
+/// On a button which has a [UndoButton] component clicked,
+/// undo the last move. The gameplay goes on since a step is given back.
+pub fn undo_button(
+    mut commands: Commands,
+    query: Query<&Interaction, (Changed<Interaction>, With<UndoButton>)>,
+    head_query: Query<(&mut TileCoords, &mut Transform), With<Head>>,
+    body_query: Query<Entity, With<Body>>,
+    mut puzzle: ResMut<CurrentPuzzle>,
+    mut operated: EventWriter<Operated>,
+) {
+    if !query.iter().any(|i| *i == Interaction::Pressed) || !puzzle.undo() {
+        return;
+    }
+    operated.write(Operated(Operation::Undo));
+    snap_to_puzzle(&mut commands, head_query, body_query, &puzzle);
+}
+
+/// On a button which has a [RestartButton] component clicked,
+/// load the same stage as now again.
+pub fn restart_button(
+    query: Query<&Interaction, (Changed<Interaction>, With<RestartButton>)>,
+    stage_manager: Res<StageManager>,
+    mut load_stage: EventWriter<LoadStage>,
+    mut operated: EventWriter<Operated>,
+) {
+    for i in query {
+        if *i == Interaction::Pressed {
+            load_stage.write(LoadStage {
+                index: stage_manager.index,
+            });
+            operated.write(Operated(Operation::Reset));
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use super::UIResource;
 
-use crate::prelude::*;
+use crate::{
+    prelude::*,
+    replay::{Operated, Operation},
+    stage_manager::LoadStage,
+};
 use bevy::prelude::*;
 
 #[derive(Component)]
@@ -11,8 +15,14 @@ pub struct ResetDurationDisplay {
 #[derive(Component)]
 pub struct StageIndexDisplay;
 
-/// Constructing stage index display and
-/// reset duration display on the upper left of a screen.
+#[derive(Component)]
+pub struct RemainingStepsDisplay;
+
+#[derive(Component)]
+pub struct PearlCountDisplay;
+
+/// Constructing stage index display, remaining steps display,
+/// pearl count display and reset duration display on the upper left of a screen.
 pub fn upper_left_menu_construction(mut commands: Commands, ucr: Res<UIResource>) {
     commands
         .spawn((Node {
@@ -30,6 +40,18 @@ pub fn upper_left_menu_construction(mut commands: Commands, ucr: Res<UIResource>
             ucr.text_font.clone(),
             StageIndexDisplay,
         ))
+        .with_child((
+            Text::new(String::new()),
+            TextColor::BLACK,
+            ucr.text_font.clone(),
+            RemainingStepsDisplay,
+        ))
+        .with_child((
+            Text::new(String::new()),
+            TextColor::BLACK,
+            ucr.text_font.clone(),
+            PearlCountDisplay,
+        ))
         .with_child((
             Text::new(String::new()),
             TextColor::BLACK,
@@ -50,7 +72,8 @@ pub fn countup_reset_duration(
     gamepad: Query<&Gamepad>,
     time: Res<Time>,
     stage_manager: Res<StageManager>,
-    mut construct_aquarium: EventWriter<ConstructAquarium>,
+    mut load_stage: EventWriter<LoadStage>,
+    mut operated: EventWriter<Operated>,
 ) {
     // Detect that was R on keyboard or North button on gamepad pressed.
     let pressed = match gamepad.single() {
@@ -63,12 +86,10 @@ pub fn countup_reset_duration(
             // If pressed time is greater than [RESET_EXPECTED_PRESSTIME],
             // call the same stage as now, and reset pressed duration.
             if duration.reset_duration > RESET_EXPECTED_PRESSTIME {
-                construct_aquarium.write(
-                    toml::from_str::<ConstructAquarium>(
-                        stage_manager.stages.get(stage_manager.index).unwrap(),
-                    )
-                    .unwrap(),
-                );
+                load_stage.write(LoadStage {
+                    index: stage_manager.index,
+                });
+                operated.write(Operated(Operation::Reset));
                 duration.reset_duration = 0.;
             }
             // Display reset duration. The amount of "." is
@@ -105,3 +126,46 @@ pub fn stage_index_display(
         };
     }
 }
+
+/// Showing how many steps are left in a stage with the limit,
+/// while the boxfish is on the screen.
+pub fn remaining_steps_display(
+    state: Res<State<MacroStates>>,
+    mut query: Query<(&mut Text, &mut Visibility), With<RemainingStepsDisplay>>,
+    puzzle: Res<CurrentPuzzle>,
+) {
+    for (mut text, mut visibility) in &mut query {
+        if puzzle.is_changed() {
+            text.0 = match puzzle.remaining_steps() {
+                Some(remaining) => format!("ノコリ{}手", remaining),
+                None => String::new(),
+            };
+        }
+        *visibility = match state.get() {
+            MacroStates::GamePlay | MacroStates::OutOfMoves => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Showing how many pearls are picked up in a stage with any pearl,
+/// while the boxfish is on the screen.
+pub fn pearl_count_display(
+    state: Res<State<MacroStates>>,
+    mut query: Query<(&mut Text, &mut Visibility), With<PearlCountDisplay>>,
+    puzzle: Res<CurrentPuzzle>,
+) {
+    for (mut text, mut visibility) in &mut query {
+        if puzzle.is_changed() {
+            let total = puzzle.layout().pearls.len();
+            text.0 = match total {
+                0 => String::new(),
+                _ => format!("シンジュ{}/{}", puzzle.collected_pearls(), total),
+            };
+        }
+        *visibility = match state.get() {
+            MacroStates::GamePlay | MacroStates::OutOfMoves => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
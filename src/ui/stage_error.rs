@@ -0,0 +1,105 @@
+use super::UIResource;
+use crate::{
+    prelude::*,
+    stage_manager::{BrokenStage, NextStage},
+};
+use bevy::prelude::*;
+
+#[derive(Component)]
+/// Skipping the broken stage, then continuing from the next one.
+pub struct SkipStageButton;
+
+#[derive(Component)]
+/// Giving up the game, then back to the main menu.
+pub struct ReturnToTitleButton;
+
+/// Constructing a panel with those parts:
+///
+/// - Which stage is broken
+/// - Why it couldn't be loaded
+/// - Skip the stage
+/// - Back to main screen
+pub fn stage_error_panel_construction(
+    mut commands: Commands,
+    broken_stage: Res<BrokenStage>,
+    ucr: Res<UIResource>,
+) {
+    let message_font = TextFont {
+        font: ucr.font.clone(),
+        font_size: 48.,
+        ..default()
+    };
+    let reason = broken_stage
+        .error
+        .as_ref()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Vw(1.),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0., 0., 0., 0.7)),
+            StateScoped(MacroStates::StageError),
+        ))
+        .with_child((
+            Text::new(format!("ステージ{}がコワレテいた…", broken_stage.index + 1)),
+            TextColor::WHITE,
+            message_font.clone(),
+        ))
+        .with_child((Text::new(reason), TextColor::WHITE, ucr.text_font.clone()))
+        .with_child((
+            Text::new("トバス"),
+            TextColor::WHITE,
+            message_font.clone(),
+            Button,
+            SkipStageButton,
+        ))
+        .with_child((
+            Text::new("タイトルにモドル"),
+            TextColor::WHITE,
+            message_font,
+            Button,
+            ReturnToTitleButton,
+        ));
+}
+
+/// On a button which has a [SkipStageButton] component clicked,
+/// forget the broken stage then call the next one.
+pub fn skip_stage_button(
+    mut next_stage: EventWriter<NextStage>,
+    mut broken_stage: ResMut<BrokenStage>,
+    query: Query<&Interaction, (Changed<Interaction>, With<SkipStageButton>)>,
+    mut state: ResMut<NextState<MacroStates>>,
+) {
+    for i in query {
+        if *i == Interaction::Pressed {
+            broken_stage.error = None;
+            next_stage.write(NextStage);
+            state.set(MacroStates::GamePlay);
+        }
+    }
+}
+
+/// On a button which has a [ReturnToTitleButton] component clicked,
+/// reset the game then back to the main menu.
+pub fn return_to_title_button(
+    mut new_game: EventWriter<NewGame>,
+    mut broken_stage: ResMut<BrokenStage>,
+    query: Query<&Interaction, (Changed<Interaction>, With<ReturnToTitleButton>)>,
+    mut state: ResMut<NextState<MacroStates>>,
+) {
+    for i in query {
+        if *i == Interaction::Pressed {
+            broken_stage.error = None;
+            new_game.write(NewGame::Continue);
+            state.set(MacroStates::ESCMenu);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Where files of the player (e.g. stage packs) are kept.
+
+use std::path::PathBuf;
+
+/// The name of the directory made under the data directory of the OS.
+const APP_DIRECTORY: &str = "BoxFishOverflow";
+
+/// Getting the directory to keep the player's files in.
+///
+/// - Windows: `%APPDATA%\BoxFishOverflow`
+/// - macOS: `~/Library/Application Support/BoxFishOverflow`
+/// - Others: `$XDG_DATA_HOME/BoxFishOverflow` or `~/.local/share/BoxFishOverflow`
+///
+/// Returns None when the home directory is unknown.
+pub fn user_data_dir() -> Option<PathBuf> {
+    let env = |key: &str| {
+        std::env::var_os(key)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(target_os = "windows") {
+        env("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        env("HOME")?.join("Library/Application Support")
+    } else {
+        env("XDG_DATA_HOME").or_else(|| Some(env("HOME")?.join(".local/share")))?
+    };
+    Some(base.join(APP_DIRECTORY))
+}